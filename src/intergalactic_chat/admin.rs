@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use serenity::http::Http;
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use serenity::model::Timestamp;
+use serenity::utils::Colour;
+
+use crate::intergalactic_chat::discord::bans::{expiry_after, parse_duration, BanEntry};
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::commands::network_ban::notify_banned;
+use crate::intergalactic_chat::discord::mod_log::ModLogEvent;
+
+/// An error status and a message explaining it, sent as `{"error": message}`.
+type ApiError = (StatusCode, String);
+
+/// Serves the admin API until the bot exits. Every request must send `token`
+/// as a bearer token.
+pub async fn serve(
+	address: SocketAddr, token: String, handler: Arc<DiscordHandler>, http: Arc<Http>,
+) {
+	let make_service = make_service_fn(move |_| {
+		let token = token.to_owned();
+		let handler = Arc::clone(&handler);
+		let http = Arc::clone(&http);
+
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+				let token = token.to_owned();
+				let handler = Arc::clone(&handler);
+				let http = Arc::clone(&http);
+
+				async move {
+					let result = if is_authorized(&request, &token) {
+						route(request, &handler, &http).await
+					} else {
+						Err((StatusCode::UNAUTHORIZED, "Invalid token".to_owned()))
+					};
+
+					let (status, body) = match result {
+						Ok(v) => (StatusCode::OK, v),
+						Err((status, error)) => (status, json!({ "error": error })),
+					};
+
+					Ok::<_, Infallible>(
+						Response::builder()
+							.status(status)
+							.header("Content-Type", "application/json")
+							.body(Body::from(body.to_string()))
+							.unwrap(),
+					)
+				}
+			}))
+		}
+	});
+
+	println!("Serving the admin API on http://{address}");
+
+	if let Err(e) = Server::bind(&address).serve(make_service).await {
+		println!("Admin API error: {e}");
+	}
+}
+
+/// Compares the bearer token without returning early, so the token can't be
+/// guessed from response times.
+fn is_authorized(request: &Request<Body>, token: &str) -> bool {
+	let provided = request
+		.headers()
+		.get("Authorization")
+		.and_then(|h| h.to_str().ok())
+		.and_then(|h| h.strip_prefix("Bearer "))
+		.unwrap_or_default();
+
+	provided.len() == token.len()
+		&& provided
+			.bytes()
+			.zip(token.bytes())
+			.fold(0, |acc, (a, b)| acc | (a ^ b))
+			== 0
+}
+
+async fn route(
+	request: Request<Body>, handler: &DiscordHandler, http: &Http,
+) -> Result<Value, ApiError> {
+	let method = request.method().to_owned();
+	let path: Vec<String> = request
+		.uri()
+		.path()
+		.split('/')
+		.filter(|s| !s.is_empty())
+		.map(str::to_owned)
+		.collect();
+	let path: Vec<&str> = path.iter().map(String::as_str).collect();
+
+	match (method, path.as_slice()) {
+		(Method::GET, ["status"]) => status(handler).await,
+		(Method::GET, ["peers"]) => peers(handler).await,
+		(Method::GET, ["channels"]) => Ok(json!(handler.config.read().await.discord.channels)),
+		(Method::POST, ["channels"]) => {
+			add_channel(&read_body(request).await?, handler, http).await
+		}
+		(Method::DELETE, ["channels", id]) => remove_channel(parse_id(id)?, handler, http).await,
+		(Method::GET, ["bans"]) => Ok(json!(handler.ban_list.lock().await.list)),
+		(Method::POST, ["bans"]) => add_ban(&read_body(request).await?, handler, http).await,
+		(Method::DELETE, ["bans", id]) => remove_ban(parse_id(id)?, handler, http).await,
+		(Method::POST, ["reload"]) => handler
+			.reload_config(http)
+			.await
+			.map(|_| json!({ "reloaded": true }))
+			.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e)),
+		(Method::POST, ["announce"]) => announce(&read_body(request).await?, handler, http).await,
+		_ => Err((StatusCode::NOT_FOUND, "Not found".to_owned())),
+	}
+}
+
+async fn status(handler: &DiscordHandler) -> Result<Value, ApiError> {
+	let config = handler.config.read().await.to_owned();
+	let mqtt_status = handler.mqtt_status.lock().await.to_owned();
+	let link_status = handler.link_status.lock().await.to_owned();
+	let message_cache = handler.message_cache.lock().await;
+	let directory = handler.peer_directory.lock().await;
+	let peers = directory.peers(&config.mqtt.client_id);
+
+	Ok(json!({
+		"connected": mqtt_status.connected_since.is_some(),
+		"connected_seconds": mqtt_status.connected_since.map(|s| s.elapsed().as_secs()),
+		"reconnects": mqtt_status.reconnects,
+		"last_error": mqtt_status.last_error,
+		"uptime_seconds": link_status.started.elapsed().as_secs(),
+		"topic": config.mqtt.topic,
+		"channels": config.discord.channels.len(),
+		"webhooks": handler.webhooks.lock().await.len(),
+		"degraded_channels": handler
+			.webhook_health
+			.degraded()
+			.await
+			.into_iter()
+			.map(|(c, f)| (c.to_string(), f.to_string()))
+			.collect::<HashMap<_, _>>(),
+		"cache": { "entries": message_cache.len(), "size": message_cache.size() },
+		"bans": handler.ban_list.lock().await.list.len(),
+		"peers_online": peers.iter().filter(|(_, p)| directory.is_online(p)).count(),
+		"peers_known": peers.len(),
+	}))
+}
+
+async fn peers(handler: &DiscordHandler) -> Result<Value, ApiError> {
+	let client_id = handler.config.read().await.mqtt.client_id.to_owned();
+	let directory = handler.peer_directory.lock().await;
+
+	Ok(directory
+		.peers(&client_id)
+		.iter()
+		.map(|(id, peer)| {
+			json!({
+				"client_id": id,
+				"online": directory.is_online(peer),
+				"last_seen_seconds": peer.last_seen.elapsed().as_secs(),
+				"heartbeat": peer.heartbeat,
+			})
+		})
+		.collect())
+}
+
+/// Links a channel until the bot is restarted or the config is reloaded, the
+/// config file isn't changed.
+async fn add_channel(
+	body: &Value, handler: &DiscordHandler, http: &Http,
+) -> Result<Value, ApiError> {
+	let channel = body_id(body, "channel")?;
+	let bot_name = bot_name(handler).await?;
+
+	match ChannelId(channel).to_channel(http).await.map(|c| c.guild()) {
+		Ok(Some(_)) => (),
+		_ => {
+			return Err(bad_request(format!(
+				"No server channel with the ID {channel} exists"
+			)))
+		}
+	}
+
+	{
+		let mut config = handler.config.write().await;
+
+		if config.discord.channels.contains(&channel) {
+			return Err((
+				StatusCode::CONFLICT,
+				"This channel is already linked".to_owned(),
+			));
+		}
+
+		config.discord.channels.push(channel);
+	}
+
+	if let Err(e) = handler.sync_links(http, &bot_name).await {
+		handler
+			.config
+			.write()
+			.await
+			.discord
+			.channels
+			.retain(|c| *c != channel);
+
+		return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+	}
+
+	Ok(json!({ "linked": channel.to_string(), "runtime_only": true }))
+}
+
+/// Unlinks a channel until the bot is restarted or the config is reloaded,
+/// the config file isn't changed.
+async fn remove_channel(
+	channel: u64, handler: &DiscordHandler, http: &Http,
+) -> Result<Value, ApiError> {
+	let bot_name = bot_name(handler).await?;
+
+	{
+		let mut config = handler.config.write().await;
+
+		if !config.discord.channels.contains(&channel) {
+			return Err((
+				StatusCode::NOT_FOUND,
+				"This channel isn't linked".to_owned(),
+			));
+		}
+
+		config.discord.channels.retain(|c| *c != channel);
+	}
+
+	handler
+		.sync_links(http, &bot_name)
+		.await
+		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+	Ok(json!({ "unlinked": channel.to_string(), "runtime_only": true }))
+}
+
+/// Bans made through the API are attributed to the bot. Their origin is the
+/// server given as `origin`, or the server of the first linked channel.
+async fn add_ban(body: &Value, handler: &DiscordHandler, http: &Http) -> Result<Value, ApiError> {
+	let user = body_id(body, "user")?;
+	let reason = match body.get("reason").and_then(Value::as_str) {
+		Some(r) if !r.trim().is_empty() => r.to_owned(),
+		_ => return Err(bad_request("Expected a reason".to_owned())),
+	};
+	let origin = match body.get("origin") {
+		Some(_) => Some(GuildId(body_id(body, "origin")?)),
+		None => None,
+	};
+	let now = Timestamp::now();
+	let expires_at = match body.get("duration").and_then(Value::as_str) {
+		Some(d) => match parse_duration(d).and_then(|d| expiry_after(now, d)) {
+			Some(e) => Some(e),
+			None => return Err(bad_request(format!("\"{d}\" is not a valid duration"))),
+		},
+		None => None,
+	};
+
+	let user = UserId(user)
+		.to_user(http)
+		.await
+		.map_err(|_| bad_request(format!("No user with the ID {user} exists")))?;
+
+	if user.bot {
+		return Err(bad_request("Bot users cannot be network banned".to_owned()));
+	}
+
+	let (executor, default_origin) = api_moderator(handler).await?;
+	let entry = BanEntry {
+		reason,
+		executor,
+		ban_origin: origin.unwrap_or(default_origin),
+		timestamp: now,
+		expires_at,
+	};
+
+	{
+		let mut ban_list = handler.ban_list.lock().await;
+
+		if ban_list.list.contains_key(&user.id) {
+			return Err((
+				StatusCode::CONFLICT,
+				"This user is already banned".to_owned(),
+			));
+		}
+
+		ban_list.list.insert(user.id, entry.to_owned());
+	}
+
+	handler.save_bans().await;
+
+	handler
+		.mod_log
+		.lock()
+		.await
+		.post_all(
+			http,
+			ModLogEvent::Ban {
+				user: &user,
+				entry: &entry,
+			},
+		)
+		.await;
+
+	let notified = notify_banned(http, &user, &entry).await;
+
+	Ok(json!({ "banned": user.id, "entry": entry, "notified": notified }))
+}
+
+/// Unbans made through the API are attributed to the bot and the server of the
+/// first linked channel.
+async fn remove_ban(user: u64, handler: &DiscordHandler, http: &Http) -> Result<Value, ApiError> {
+	let (executor, origin) = api_moderator(handler).await?;
+	let entry = match handler.ban_list.lock().await.list.remove(&UserId(user)) {
+		Some(e) => e,
+		None => return Err((StatusCode::NOT_FOUND, "This user isn't banned".to_owned())),
+	};
+
+	handler.save_bans().await;
+
+	if let Ok(user) = UserId(user).to_user(http).await {
+		handler
+			.mod_log
+			.lock()
+			.await
+			.post_all(
+				http,
+				ModLogEvent::Unban {
+					user: &user,
+					executor,
+					origin,
+					entry: &entry,
+				},
+			)
+			.await;
+	}
+
+	Ok(json!({ "unbanned": user.to_string() }))
+}
+
+/// The user and server that moderation through the API is attributed to: the
+/// bot, and the server of the first linked channel.
+async fn api_moderator(handler: &DiscordHandler) -> Result<(UserId, GuildId), ApiError> {
+	let executor = match handler.heartbeat.lock().await.as_ref() {
+		Some(h) => UserId(h.bot_id),
+		None => return Err(not_ready()),
+	};
+	let first_channel = handler
+		.config
+		.read()
+		.await
+		.discord
+		.channels
+		.first()
+		.copied();
+	let origin = match first_channel {
+		Some(c) => handler.mod_log.lock().await.guild_of(ChannelId(c)),
+		None => None,
+	};
+
+	match origin {
+		Some(origin) => Ok((executor, origin)),
+		None => Err((
+			StatusCode::SERVICE_UNAVAILABLE,
+			"The server of the first linked channel isn't known yet".to_owned(),
+		)),
+	}
+}
+
+/// Posts an announcement embed, as the bot, in every linked channel.
+async fn announce(body: &Value, handler: &DiscordHandler, http: &Http) -> Result<Value, ApiError> {
+	let content = match body.get("content").and_then(Value::as_str) {
+		Some(c) if !c.trim().is_empty() => c.to_owned(),
+		_ => {
+			return Err(bad_request(
+				"Expected the content of the announcement".to_owned(),
+			))
+		}
+	};
+	let channels = handler.config.read().await.discord.channels.to_owned();
+	let mut failed = Vec::new();
+
+	for channel in &channels {
+		let sent = ChannelId(*channel)
+			.send_message(http, |m| {
+				m.embed(|e| {
+					e.title("Announcement")
+						.description(&content)
+						.colour(Colour::BLURPLE)
+				})
+			})
+			.await;
+
+		if let Err(e) = sent {
+			println!("Error posting announcement in {channel}: {e}");
+			failed.push(channel.to_string());
+		}
+	}
+
+	Ok(json!({ "sent": channels.len() - failed.len(), "failed": failed }))
+}
+
+async fn read_body(request: Request<Body>) -> Result<Value, ApiError> {
+	let body = hyper::body::to_bytes(request.into_body())
+		.await
+		.map_err(|e| bad_request(e.to_string()))?;
+
+	serde_json::from_slice(&body).map_err(|e| bad_request(format!("Invalid JSON: {e}")))
+}
+
+/// Reads an ID from `body`, which may be a string or a number.
+fn body_id(body: &Value, key: &str) -> Result<u64, ApiError> {
+	match body.get(key) {
+		Some(Value::String(id)) => parse_id(id),
+		Some(Value::Number(id)) => id
+			.as_u64()
+			.filter(|id| *id != 0)
+			.ok_or_else(|| bad_request(format!("{id} is not a valid ID"))),
+		_ => Err(bad_request(format!("Expected `{key}`"))),
+	}
+}
+
+fn parse_id(id: &str) -> Result<u64, ApiError> {
+	id.trim()
+		.parse::<u64>()
+		.ok()
+		.filter(|id| *id != 0)
+		.ok_or_else(|| bad_request(format!("{id} is not a valid ID")))
+}
+
+async fn bot_name(handler: &DiscordHandler) -> Result<String, ApiError> {
+	match handler.heartbeat.lock().await.as_ref() {
+		Some(h) => Ok(h.name.to_owned()),
+		None => Err(not_ready()),
+	}
+}
+
+fn bad_request(message: String) -> ApiError {
+	(StatusCode::BAD_REQUEST, message)
+}
+
+fn not_ready() -> ApiError {
+	(
+		StatusCode::SERVICE_UNAVAILABLE,
+		"The bot isn't ready yet".to_owned(),
+	)
+}
@@ -0,0 +1,257 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use rand::distributions::{Alphanumeric, DistString};
+use serenity::model::prelude::UserId;
+use serenity::model::Timestamp;
+
+use crate::intergalactic_chat::config::{Config, DEFAULT_CONFIG, ENV_PREFIX};
+use crate::intergalactic_chat::data::DataDir;
+use crate::intergalactic_chat::discord::bans::{
+	expiry_after, parse_duration, BanEntry, BanList, CLI_ORIGIN,
+};
+
+pub const USAGE: &str = "\
+Usage: discord-intergalactic-chat-link [COMMAND] [OPTIONS]
+
+Commands:
+  run                           Starts the bot, the default if no command is given.
+  init [--force]                Writes the default config, --force replaces an existing one.
+  check-config                  Checks the config is valid without connecting.
+  bans list                     Lists the network bans.
+  bans add <USER ID> <REASON>   Bans a user, permanently unless --duration is set.
+  bans remove <USER ID>         Lifts a user's ban.
+  keygen                        Generates a token for the admin API.
+  help                          Shows this message.
+
+Options:
+  -c, --config <PATH>           The config file, config.toml by default.
+  -d, --data-dir <PATH>         Where bans, mutes and the message cache are kept, ICL_DATA_DIR or the
+                                current directory by default.
+      --duration <DURATION>     How long a ban lasts, such as 30m, 12h or 7d.
+
+The bans commands edit the ban file directly, so only use them while the bot is stopped.";
+
+pub enum Command {
+	Run,
+	Init {
+		force: bool,
+	},
+	CheckConfig,
+	BansList,
+	BansAdd {
+		user: UserId,
+		reason: String,
+		duration: Option<String>,
+	},
+	BansRemove {
+		user: UserId,
+	},
+	Keygen,
+	Help,
+}
+
+/// The parsed command-line arguments.
+pub struct Cli {
+	pub command: Command,
+	pub config: String,
+	pub data_dir: DataDir,
+}
+
+impl Cli {
+	/// Parses the arguments, not including the name of the binary.
+	pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+		let mut args = args.into_iter();
+		let mut positional = Vec::new();
+		let mut config = String::from("config.toml");
+		let mut data_dir = std::env::var_os("ICL_DATA_DIR")
+			.map(PathBuf::from)
+			.unwrap_or_else(|| PathBuf::from("."));
+		let mut duration = None;
+		let mut force = false;
+
+		while let Some(arg) = args.next() {
+			let mut value = |flag: &str| args.next().ok_or(format!("{flag} expects a value"));
+
+			match arg.as_str() {
+				"-c" | "--config" => config = value(&arg)?,
+				"-d" | "--data-dir" => data_dir = PathBuf::from(value(&arg)?),
+				"--duration" => duration = Some(value(&arg)?),
+				"--force" => force = true,
+				"-h" | "--help" => positional.insert(0, "help".to_owned()),
+				flag if flag.starts_with('-') => return Err(format!("Unknown option {flag}")),
+				_ => positional.push(arg),
+			}
+		}
+
+		let positional: Vec<&str> = positional.iter().map(String::as_str).collect();
+		let command = match positional.as_slice() {
+			[] | ["run"] => Command::Run,
+			["init"] => Command::Init { force },
+			["check-config"] => Command::CheckConfig,
+			["bans", "list"] => Command::BansList,
+			["bans", "add", user, reason @ ..] if !reason.is_empty() => Command::BansAdd {
+				user: parse_user(user)?,
+				reason: reason.join(" "),
+				duration,
+			},
+			["bans", "add", ..] => return Err("bans add expects a user ID and a reason".to_owned()),
+			["bans", "remove", user] => Command::BansRemove {
+				user: parse_user(user)?,
+			},
+			["keygen"] => Command::Keygen,
+			["help", ..] => Command::Help,
+			_ => return Err(format!("Unknown command \"{}\"", positional.join(" "))),
+		};
+
+		Ok(Self {
+			command,
+			config,
+			data_dir: DataDir::new(data_dir),
+		})
+	}
+}
+
+fn parse_user(id: &str) -> Result<UserId, String> {
+	id.trim()
+		.parse::<u64>()
+		.ok()
+		.filter(|id| *id != 0)
+		.map(UserId)
+		.ok_or(format!("{id} is not a valid user ID"))
+}
+
+/// Writes [`DEFAULT_CONFIG`] to `path`, refusing to replace an existing config
+/// unless `force` is set.
+pub fn init(path: &str, force: bool) -> Result<(), String> {
+	let mut options = OpenOptions::new();
+	options.write(true);
+
+	if force {
+		options.create(true).truncate(true);
+	} else {
+		options.create_new(true);
+	}
+
+	options
+		.open(path)
+		.and_then(|mut f| f.write_all(DEFAULT_CONFIG.as_bytes()))
+		.map_err(|e| format!("Unable to write {path}: {e}, use --force to replace it"))?;
+
+	println!("Wrote the default config to {path}, fill it in before starting the bot.");
+
+	Ok(())
+}
+
+pub fn check_config(path: &str) -> Result<(), String> {
+	let config = Config::load(path)?;
+
+	println!(
+		"{path} is valid, linking {} channels on the topic \"{}\" through {}:{}.",
+		config.discord.channels.len(),
+		config.mqtt.topic,
+		config.mqtt.broker_ip,
+		config.mqtt.broker_port
+	);
+	println!(
+		"\nValues are read from, highest precedence first:\n  \
+		1. {ENV_PREFIX}_<TABLE>_<KEY> environment variables, such as {ENV_PREFIX}_DISCORD_TOKEN, or\n     \
+		{ENV_PREFIX}_<TABLE>_<KEY>_FILE to read the value from the file it points to.\n     \
+		Both can't be set for the same value, neither takes precedence over the other.\n  \
+		2. {path}\n  \
+		3. The built-in defaults"
+	);
+
+	if !config.overrides.is_empty() {
+		println!("\nSet by environment variables:");
+
+		for o in &config.overrides {
+			println!("  {} from {}", o.key, o.variable);
+		}
+	}
+
+	Ok(())
+}
+
+pub fn bans(cli: &Cli) -> Result<(), String> {
+	cli.data_dir.create()?;
+	let path = cli.data_dir.bans();
+	let mut ban_list = BanList::initialize(&path);
+
+	match &cli.command {
+		Command::BansList => {
+			if ban_list.list.is_empty() {
+				println!("Nobody is banned from the network.");
+			}
+
+			for (user, entry) in &ban_list.list {
+				println!(
+					"{user}: {} (by {} from {}, banned {}, expires {})",
+					entry.reason,
+					entry.executor,
+					entry.display_origin(),
+					entry.timestamp,
+					entry
+						.expires_at
+						.map(|e| e.to_string())
+						.unwrap_or_else(|| "never".to_owned())
+				);
+			}
+
+			return Ok(());
+		}
+		Command::BansAdd {
+			user,
+			reason,
+			duration,
+		} => {
+			if ban_list.list.contains_key(user) {
+				return Err(format!("{user} is already banned"));
+			}
+
+			// Bans made offline are attributed to the bot.
+			let executor = UserId(Config::load(&cli.config)?.discord.bot_id);
+			let now = Timestamp::now();
+			let expires_at = match duration {
+				Some(d) => Some(
+					parse_duration(d)
+						.and_then(|d| expiry_after(now, d))
+						.ok_or(format!("\"{d}\" is not a valid duration"))?,
+				),
+				None => None,
+			};
+
+			ban_list.list.insert(
+				*user,
+				BanEntry {
+					reason: reason.to_owned(),
+					executor,
+					ban_origin: CLI_ORIGIN,
+					timestamp: now,
+					expires_at,
+				},
+			);
+			println!("Banned {user}.");
+		}
+		Command::BansRemove { user } => {
+			if ban_list.list.remove(user).is_none() {
+				return Err(format!("{user} isn't banned"));
+			}
+
+			println!("Unbanned {user}.");
+		}
+		_ => unreachable!("Not a bans command"),
+	}
+
+	ban_list
+		.write_to_file(&path)
+		.map_err(|e| format!("Unable to write {}: {e}", path.display()))
+}
+
+/// Prints a random token for the admin API.
+pub fn keygen() {
+	let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 48);
+
+	println!("{token}\n\nSet this as the `token` in the [admin] table of your config.");
+}
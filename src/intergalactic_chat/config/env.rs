@@ -0,0 +1,110 @@
+use std::env;
+use std::fs;
+
+use toml::value::{Table, Value};
+
+use super::Config;
+
+/// The prefix of the environment variables that override config values.
+pub const ENV_PREFIX: &str = "ICL";
+
+/// A config value that was set by an environment variable instead of the
+/// config file.
+#[derive(Debug, Clone)]
+pub struct Override {
+	/// The dotted path of the value, such as `discord.token`.
+	pub key: String,
+	/// The variable the value was read from, such as `ICL_DISCORD_TOKEN_FILE`.
+	pub variable: String,
+}
+
+impl Config {
+	/// Overrides the values of the config with the `ICL_*` environment
+	/// variables. `ICL_DISCORD_TOKEN` sets `token` in the `[discord]` table,
+	/// and `ICL_DISCORD_TOKEN_FILE` reads it from a file instead, for secrets
+	/// mounted by Docker or Kubernetes. Arrays are separated by commas.
+	pub fn apply_env(self) -> Result<Config, String> {
+		let mut value = Value::try_from(&self)
+			.map_err(|e| format!("Unable to apply environment variables: {e}"))?;
+		let mut overrides = Vec::new();
+
+		if let Value::Table(table) = &mut value {
+			override_table(table, ENV_PREFIX, "", &mut overrides)?;
+		}
+
+		if overrides.is_empty() {
+			return Ok(self);
+		}
+
+		let mut config = value
+			.try_into::<Config>()
+			.map_err(|e| format!("The environment variables are invalid: {e}"))?;
+		config.overrides = overrides;
+
+		Ok(config)
+	}
+}
+
+/// Overrides every value in `table`, recursing into nested tables.
+fn override_table(
+	table: &mut Table, prefix: &str, path: &str, overrides: &mut Vec<Override>,
+) -> Result<(), String> {
+	for (key, value) in table.iter_mut() {
+		let variable = format!("{prefix}_{}", key.to_uppercase());
+		let key = match path {
+			"" => key.to_owned(),
+			path => format!("{path}.{key}"),
+		};
+
+		if let Value::Table(nested) = value {
+			override_table(nested, &variable, &key, overrides)?;
+			continue;
+		}
+
+		let file_variable = format!("{variable}_FILE");
+		let (raw, variable) = match (env::var(&variable), env::var(&file_variable)) {
+			(Ok(_), Ok(_)) => {
+				return Err(format!(
+					"Both {variable} and {file_variable} are set, only set one of them"
+				))
+			}
+			(Ok(raw), Err(_)) => (raw, variable),
+			(Err(_), Ok(file)) => (
+				fs::read_to_string(&file)
+					.map_err(|e| format!("Error trying to read {file} from {file_variable}: {e}"))?
+					.trim_end_matches(['\r', '\n'])
+					.to_owned(),
+				file_variable,
+			),
+			(Err(_), Err(_)) => continue,
+		};
+
+		*value = parse(value, &raw).ok_or(format!(
+			"{variable} is \"{raw}\", which isn't a valid value for `{key}`"
+		))?;
+		overrides.push(Override { key, variable });
+	}
+
+	Ok(())
+}
+
+/// Parses `raw` as the same type as `current`.
+fn parse(current: &Value, raw: &str) -> Option<Value> {
+	match current {
+		Value::String(_) => Some(Value::String(raw.to_owned())),
+		Value::Integer(_) => raw.trim().parse().ok().map(Value::Integer),
+		Value::Float(_) => raw.trim().parse().ok().map(Value::Float),
+		Value::Boolean(_) => raw.trim().parse().ok().map(Value::Boolean),
+		Value::Array(_) => Some(Value::Array(
+			raw.split(',')
+				.map(str::trim)
+				.filter(|item| !item.is_empty())
+				.map(|item| match item.parse() {
+					Ok(i) => Value::Integer(i),
+					Err(_) => Value::String(item.to_owned()),
+				})
+				.collect(),
+		)),
+		Value::Datetime(_) | Value::Table(_) => None,
+	}
+}
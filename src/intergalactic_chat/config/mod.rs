@@ -1,38 +1,18 @@
 use serde::{Deserialize, Serialize};
-use std::fs::File;
+use std::collections::HashMap;
 use std::fs::OpenOptions;
-use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Write;
-use std::ops::Deref;
-use toml::toml;
+use std::time::Duration;
 
-/// Struct representing the bot's configuration.
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Config {
-	pub mqtt: Mqtt,
-	pub discord: Discord,
-}
+mod env;
+mod validation;
 
-impl Config {
-	/// Attempts to read the config file (`path`). If the file does not exist
-	/// it attempts to create a new one and write an empty config. Reads and
-	/// then returns a [`Config`].
-	///
-	/// ## Panics
-	///
-	/// This function will panic if:
-	///
-	/// - There is an error opening the file.
-	/// - There is an error creating the file.
-	/// - There is an error serializing a new [`Config`] to TOML.
-	/// - There is an error deserializing from the file.
-	/// - There is an error writing the file.
-	/// - There is an error reading the file.
-	pub fn initialize(path: &str) -> Result<Config, ()> {
-		let mut buf = String::new();
-		let default_config = String::from(
-			r#"
+pub use env::{Override, ENV_PREFIX};
+
+/// The config written when the config file is empty, or by the `init`
+/// command.
+pub const DEFAULT_CONFIG: &str = r#"
 # This is the configuration file for your bot, make sure it is valid
 # before starting the bot.
 
@@ -44,39 +24,163 @@ broker_ip = "localhost"				# The IP address of your broker server.
 broker_port = 1883						# The port the server is using, by default "1883".
 client_id = "bot"							# The client ID used to connect to the MQTT server.
 topic = "example/topic"				# The topic you wish to send / receive messages through.
+presence_interval = 30				# Seconds between heartbeats telling other bots this one is online.
 
 [discord]
-bot_id = 0000000000000000000	# The application ID of your bot, found via the Discord Developer Portal.
+bot_id = 0										# The application ID of your bot, found via the Discord Developer Portal.
 # A list of channels IDs for channels you wish for the bot to link, 
 # separated by commas.
 # You can have any number of channels on any number of servers, but the
 # bot must have access to them and be able to create a webhook.
 channels = [
-	0000000000000000000,
-	0000000000000000000,
-	0000000000000000000,
+	0,
+	0,
+	0,
 ]
 # The bot's token, found via the Discord Developer portal.
 # If you are reporting an issue make sure to omit this value!
 token = "XXXXXXXXXXXXXXXXXXXXXXXXXX.XXXXXX.XXXXXXXX-XXXXXXXXXXXXXXXXXXXXXXXXXXXXX"
-		"#,
-		);
+# A list of channel IDs the bot posts moderation events to, such as bans and
+# filtered messages. Each server can have one mod-log channel.
+mod_log_channels = []
+# What happens when a moderator deletes a message copied from another server.
+# "local" only removes it here, "propagate" also deletes the original and
+# every other copy, on servers whose bots use "propagate" too.
+mirror_deletions = "local"
+# The most messages posted at once across every linked channel. Messages in
+# each channel are always posted in the order they were received.
+max_concurrent_sends = 8
+# Where the slash commands are registered, one of:
+#   "guild"   separately on each server with a linked channel.
+#   "global"  once for every server the bot is on.
+commands = "guild"
+
+[filter]
+rules = "filters.toml"				# The file containing the content filter rules.
+filter_received = false				# Also filter messages received from other bots on the network.
+
+# Limits on how many messages can be sent within a number of seconds, set
+# `messages` to 0 to disable a limit.
+[rate_limit]
+user = { messages = 5, seconds = 10 }			# Messages sent by each user.
+channel = { messages = 20, seconds = 10 }	# Messages sent in each linked channel.
+peer = { messages = 40, seconds = 10 }		# Messages received from each bot on the network.
+
+[permissions]
+owners = []						# IDs of users who can use every command on every server.
+moderator_roles = []	# IDs of roles whose members are trusted to moderate the network.
+
+# The lowest tier that can use each command, one of "everyone", "moderator"
+# or "owner". Commands not listed here can be used by everyone, as long as
+# the server's own permissions allow it. Once you have set `owners` or
+# `moderator_roles`, change these to "moderator" so only they can moderate
+# the network.
+[permissions.commands]
+network-ban = "everyone"
+network-unban = "everyone"
+network-bans = "everyone"
+
+# The message cache remembers the mirrors of each message, so edits and
+# deletions can be mirrored too.
+[cache]
+size = 100				# The number of messages to remember, the oldest are forgotten first.
+max_age = 0				# Seconds to remember a message for, 0 remembers it until the cache is full.
+snapshot_interval = 60	# Seconds between saves of the message cache, 0 only saves it on shutdown.
+
+[metrics]
+enabled = false				# Serve Prometheus metrics over HTTP.
+address = "127.0.0.1:9184"	# The address to serve the metrics on, at /metrics.
+
+[admin]
+enabled = false				# Serve the admin API over HTTP.
+address = "127.0.0.1:9185"	# The address to serve the admin API on.
+token = ""					# Requests must send this as a bearer token.
+"#;
+
+/// Struct representing the bot's configuration.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Config {
+	pub mqtt: Mqtt,
+	pub discord: Discord,
+	#[serde(default)]
+	pub filter: Filter,
+	#[serde(default)]
+	pub rate_limit: RateLimit,
+	#[serde(default)]
+	pub permissions: Permissions,
+	#[serde(default)]
+	pub cache: Cache,
+	#[serde(default)]
+	pub metrics: Metrics,
+	#[serde(default)]
+	pub admin: Admin,
+	/// The values set by environment variables rather than the config file.
+	#[serde(skip)]
+	pub overrides: Vec<Override>,
+}
+
+impl Config {
+	/// Attempts to read the config file (`path`). If the file does not exist
+	/// or is empty, the default config is written to it, which is only valid
+	/// if the environment variables fill in the rest.
+	pub fn initialize(path: &str) -> Result<Config, String> {
+		let mut buf = String::new();
 		let mut file = OpenOptions::new()
 			.create(true)
+			.truncate(false)
 			.read(true)
 			.write(true)
 			.open(path)
-			.expect(&format!("Error trying to open {path}"));
+			.map_err(|e| format!("Error trying to open {path}: {e}"))?;
 		file.read_to_string(&mut buf)
-			.expect(&format!("Error trying to read {path}"));
+			.map_err(|e| format!("Error trying to read {path}: {e}"))?;
+
+		if buf.is_empty() {
+			file.write_all(DEFAULT_CONFIG.as_bytes())
+				.map_err(|e| format!("Error trying to write to {path}: {e}"))?;
+			println!("Wrote the default config to {path}.");
+			buf = DEFAULT_CONFIG.to_owned();
+		}
+
+		Self::parse(path, &buf)
+	}
 
-		if buf.len() == 0 {
-			file.write_all(default_config.as_bytes())
-				.expect(&format!("Error trying to write to {path}"));
+	/// Reads the config file (`path`) without creating it, used to reload the
+	/// config while the bot is running.
+	pub fn load(path: &str) -> Result<Config, String> {
+		let mut buf = String::new();
 
-			Ok(toml::from_str::<Self>(&default_config).expect("Your configuration is invalid, double check you have entered the correct information"))
+		OpenOptions::new()
+			.read(true)
+			.open(path)
+			.and_then(|mut f| f.read_to_string(&mut buf))
+			.map_err(|e| format!("Error trying to read {path}: {e}"))?;
+
+		Self::parse(path, &buf)
+	}
+
+	/// Deserializes `source`, read from `path`, applies the environment
+	/// variables and validates the result. The error lists every problem
+	/// found, one per line.
+	fn parse(path: &str, source: &str) -> Result<Config, String> {
+		let config = toml::from_str::<Self>(source)
+			.map_err(|e| format!("{path} is invalid: {}", e.to_string().trim_end()))?
+			.apply_env()?;
+		let errors = config.validate(source);
+
+		if errors.is_empty() {
+			Ok(config)
 		} else {
-			Ok(toml::from_str::<Self>(buf.deref()).expect("Your configuration is invalid, double check you have entered the correct information"))
+			Err(format!(
+				"{path} has {} problem{}:\n{}",
+				errors.len(),
+				if errors.len() == 1 { "" } else { "s" },
+				errors
+					.iter()
+					.map(|e| format!("  {e}"))
+					.collect::<Vec<_>>()
+					.join("\n")
+			))
 		}
 	}
 }
@@ -88,6 +192,13 @@ pub struct Mqtt {
 	pub broker_ip: String,
 	pub broker_port: u16,
 	pub topic: String,
+	/// Seconds between the heartbeats published to the presence topic.
+	#[serde(default = "default_presence_interval")]
+	pub presence_interval: u64,
+}
+
+fn default_presence_interval() -> u64 {
+	30
 }
 
 /// Struct for configuring the Discord client.
@@ -96,4 +207,194 @@ pub struct Discord {
 	pub channels: Vec<u64>,
 	pub bot_id: u64,
 	pub token: String,
+	/// Channels moderation events are posted to, at most one per guild.
+	#[serde(default)]
+	pub mod_log_channels: Vec<u64>,
+	/// What happens when a moderator deletes a mirrored message.
+	#[serde(default)]
+	pub mirror_deletions: MirrorDeletions,
+	/// The most messages posted at once across every linked channel.
+	#[serde(default = "default_max_concurrent_sends")]
+	pub max_concurrent_sends: usize,
+	/// Where the slash commands are registered.
+	#[serde(default)]
+	pub commands: CommandScope,
+}
+
+fn default_max_concurrent_sends() -> usize {
+	8
+}
+
+/// How moderator deletions of mirrored messages are handled.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MirrorDeletions {
+	/// Only the deleted mirror is removed.
+	#[default]
+	Local,
+	/// The original message and every other mirror are deleted too.
+	Propagate,
+}
+
+/// Where the slash commands are registered.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandScope {
+	/// Registered separately on each server with a linked channel.
+	#[default]
+	Guild,
+	/// Registered once for every server the bot is on.
+	Global,
+}
+
+/// Struct for configuring the content filter.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Filter {
+	/// Path to the file containing the filter rules.
+	pub rules: String,
+	/// Whether messages received from other bots are filtered as well as those
+	/// sent from linked channels.
+	pub filter_received: bool,
+	/// Replaced by `discord.mod_log_channels`, only read so configs still
+	/// setting it are rejected rather than silently ignored.
+	#[serde(skip_serializing)]
+	pub mod_log_channel: Option<u64>,
+}
+
+impl Default for Filter {
+	fn default() -> Self {
+		Self {
+			rules: String::from("filters.toml"),
+			filter_received: false,
+			mod_log_channel: None,
+		}
+	}
+}
+
+/// Struct for configuring rate limits.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RateLimit {
+	pub user: Limit,
+	pub channel: Limit,
+	pub peer: Limit,
+}
+
+impl Default for RateLimit {
+	fn default() -> Self {
+		Self {
+			user: Limit {
+				messages: 5,
+				seconds: 10,
+			},
+			channel: Limit {
+				messages: 20,
+				seconds: 10,
+			},
+			peer: Limit {
+				messages: 40,
+				seconds: 10,
+			},
+		}
+	}
+}
+
+/// A number of messages allowed within a number of seconds.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Limit {
+	pub messages: u32,
+	pub seconds: u64,
+}
+
+/// Struct for configuring the Prometheus metrics endpoint.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Metrics {
+	pub enabled: bool,
+	/// The address the metrics are served on, at `/metrics`.
+	pub address: String,
+}
+
+impl Default for Metrics {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			address: String::from("127.0.0.1:9184"),
+		}
+	}
+}
+
+/// Struct for configuring the message cache.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Cache {
+	/// The number of messages remembered.
+	pub size: usize,
+	/// Seconds a message is remembered for, 0 remembers messages until they're
+	/// pushed out by newer ones.
+	pub max_age: u64,
+	/// Seconds between saves of the cache, which are skipped if it hasn't
+	/// changed. If 0 the cache is only saved on shutdown.
+	pub snapshot_interval: u64,
+}
+
+impl Default for Cache {
+	fn default() -> Self {
+		Self {
+			size: 100,
+			max_age: 0,
+			snapshot_interval: 60,
+		}
+	}
+}
+
+impl Cache {
+	/// [`Cache::max_age`] as a duration, `None` if messages don't expire.
+	pub fn max_age(&self) -> Option<Duration> {
+		(self.max_age > 0).then(|| Duration::from_secs(self.max_age))
+	}
+}
+
+/// Struct for configuring the local admin API.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Admin {
+	pub enabled: bool,
+	/// The address the API is served on.
+	pub address: String,
+	/// Requests must send this as a bearer token, the API won't start without
+	/// one.
+	pub token: String,
+}
+
+impl Default for Admin {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			address: String::from("127.0.0.1:9185"),
+			token: String::new(),
+		}
+	}
+}
+
+/// Struct for configuring who can use each command.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Permissions {
+	/// Users who can use every command on every server.
+	pub owners: Vec<u64>,
+	/// Roles whose members are trusted to moderate the network.
+	pub moderator_roles: Vec<u64>,
+	/// The lowest tier that can use each command, by command name.
+	pub commands: HashMap<String, PermissionTier>,
+}
+
+/// A level of trust, each tier can use the commands of the tiers below it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionTier {
+	Everyone,
+	Moderator,
+	Owner,
 }
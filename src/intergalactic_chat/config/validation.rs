@@ -0,0 +1,323 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::net::SocketAddr;
+
+use super::{Config, DEFAULT_CONFIG};
+use crate::intergalactic_chat::discord::filter::ContentFilter;
+
+/// A problem with a config value, and how to fix it.
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+	/// The dotted path of the value, such as `discord.token`.
+	pub key: String,
+	/// The line the value is set on, if it was found in the file.
+	pub line: Option<usize>,
+	pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.line {
+			Some(line) => write!(f, "line {line}: `{}` {}", self.key, self.message),
+			None => write!(f, "`{}` {}", self.key, self.message),
+		}
+	}
+}
+
+impl Config {
+	/// Checks the values of the config, which was read from `source`, for
+	/// mistakes that would stop the bot from working. Returns every problem
+	/// found.
+	pub fn validate(&self, source: &str) -> Vec<ConfigError> {
+		// The template is fine if the environment fills it in.
+		if self.overrides.is_empty() && source.trim() == DEFAULT_CONFIG.trim() {
+			return vec![ConfigError {
+				key: "config".to_owned(),
+				line: None,
+				message: "is still the default template, fill in the [mqtt] and [discord] tables before starting the bot".to_owned(),
+			}];
+		}
+
+		let mut errors = Vec::new();
+		let mut error = |table: &str, name: &str, line: Option<usize>, message: String| {
+			let key = format!("{table}.{name}");
+			// Values from the environment aren't on any line of the file.
+			let overridden = self
+				.overrides
+				.iter()
+				.find(|o| o.key == key || o.key.starts_with(&format!("{key}.")));
+			let (line, message) = match overridden {
+				Some(o) => (None, format!("{message} (set by {})", o.variable)),
+				None => (line.or_else(|| line_of(source, table, name)), message),
+			};
+
+			errors.push(ConfigError { key, line, message })
+		};
+
+		let mqtt = &self.mqtt;
+
+		if mqtt.client_id.trim().is_empty() {
+			error(
+				"mqtt",
+				"client_id",
+				None,
+				"is empty, every bot on the broker needs its own client ID".to_owned(),
+			);
+		}
+
+		if mqtt.broker_ip.trim().is_empty() {
+			error(
+				"mqtt",
+				"broker_ip",
+				None,
+				"is empty, set it to the address of your MQTT broker".to_owned(),
+			);
+		}
+
+		if mqtt.broker_port == 0 {
+			error(
+				"mqtt",
+				"broker_port",
+				None,
+				"is 0, the default MQTT port is 1883".to_owned(),
+			);
+		}
+
+		if mqtt.topic.trim().is_empty() {
+			error(
+				"mqtt",
+				"topic",
+				None,
+				"is empty, every bot on the network must use the same topic".to_owned(),
+			);
+		} else if mqtt.topic.contains(['+', '#']) {
+			error(
+				"mqtt",
+				"topic",
+				None,
+				format!("\"{}\" contains a wildcard (`+` or `#`), messages can't be published to a wildcard topic", mqtt.topic),
+			);
+		} else if mqtt.topic.starts_with('/') || mqtt.topic.ends_with('/') {
+			error(
+				"mqtt",
+				"topic",
+				None,
+				format!(
+					"\"{}\" starts or ends with `/`, which creates an empty topic level",
+					mqtt.topic
+				),
+			);
+		}
+
+		if mqtt.presence_interval == 0 {
+			error(
+				"mqtt",
+				"presence_interval",
+				None,
+				"is 0, heartbeats must be at least a second apart".to_owned(),
+			);
+		}
+
+		let discord = &self.discord;
+		let token = discord.token.trim();
+
+		if token.is_empty() || token.contains("XXXX") {
+			error(
+				"discord",
+				"token",
+				None,
+				"is a placeholder, set it to your bot's token from the Discord Developer Portal"
+					.to_owned(),
+			);
+		} else if token.split('.').count() != 3 {
+			error(
+				"discord",
+				"token",
+				None,
+				"doesn't look like a Discord bot token, which has three parts separated by `.`"
+					.to_owned(),
+			);
+		}
+
+		if discord.bot_id == 0 {
+			error(
+				"discord",
+				"bot_id",
+				None,
+				"is 0, set it to your bot's application ID from the Discord Developer Portal"
+					.to_owned(),
+			);
+		}
+
+		if discord.channels.is_empty() {
+			error(
+				"discord",
+				"channels",
+				None,
+				"is empty, add the IDs of the channels to link".to_owned(),
+			);
+		}
+
+		if discord.max_concurrent_sends == 0 {
+			error(
+				"discord",
+				"max_concurrent_sends",
+				None,
+				"is 0, so no messages could be posted, the default is 8".to_owned(),
+			);
+		}
+
+		for (key, ids) in [
+			("channels", &discord.channels),
+			("mod_log_channels", &discord.mod_log_channels),
+		] {
+			let lines = array_lines(source, "discord", key);
+			let mut seen = HashSet::new();
+
+			for (i, id) in ids.iter().enumerate() {
+				let line = lines.get(i).copied();
+
+				if *id == 0 {
+					error(
+						"discord",
+						key,
+						line,
+						"contains the channel ID 0, replace it with a real channel ID".to_owned(),
+					);
+				} else if !seen.insert(id) {
+					error(
+						"discord",
+						key,
+						line,
+						format!("contains the channel {id} more than once"),
+					);
+				}
+			}
+		}
+
+		if let Some(channel) = self.filter.mod_log_channel {
+			error(
+				"filter",
+				"mod_log_channel",
+				None,
+				format!("has been replaced, remove it and add {channel} to `discord.mod_log_channels` instead, every filter hit is posted there"),
+			);
+		}
+
+		// A missing rules file is created with the default rules.
+		if let Ok(rules) = std::fs::read_to_string(&self.filter.rules) {
+			if let Err(e) = ContentFilter::parse(&rules) {
+				error(
+					"filter",
+					"rules",
+					None,
+					format!(
+						"points to {}, whose rules are invalid: {e}",
+						self.filter.rules
+					),
+				);
+			}
+		}
+
+		for (key, limit) in [
+			("user", self.rate_limit.user),
+			("channel", self.rate_limit.channel),
+			("peer", self.rate_limit.peer),
+		] {
+			if limit.messages > 0 && limit.seconds == 0 {
+				error("rate_limit", key, None, "allows messages within 0 seconds, set `seconds` to at least 1 or `messages` to 0 to disable the limit".to_owned());
+			}
+		}
+
+		if self.cache.size == 0 {
+			error(
+				"cache",
+				"size",
+				None,
+				"is 0, so edits and deletions can't be mirrored, the default is 100".to_owned(),
+			);
+		}
+
+		if self.metrics.enabled && self.metrics.address.parse::<SocketAddr>().is_err() {
+			error(
+				"metrics",
+				"address",
+				None,
+				format!(
+					"\"{}\" is not an address and port, such as 127.0.0.1:9184",
+					self.metrics.address
+				),
+			);
+		}
+
+		if self.admin.enabled {
+			if self.admin.address.parse::<SocketAddr>().is_err() {
+				error(
+					"admin",
+					"address",
+					None,
+					format!(
+						"\"{}\" is not an address and port, such as 127.0.0.1:9185",
+						self.admin.address
+					),
+				);
+			}
+
+			if self.admin.token.trim().is_empty() {
+				error(
+					"admin",
+					"token",
+					None,
+					"is empty, generate one with the `keygen` command".to_owned(),
+				);
+			}
+		}
+
+		errors
+	}
+}
+
+/// The line `key` is set on in `table`, counting from 1.
+fn line_of(source: &str, table: &str, key: &str) -> Option<usize> {
+	let mut current = "";
+
+	for (i, line) in source.lines().enumerate() {
+		let line = line.trim();
+
+		if let Some(header) = line.strip_prefix('[') {
+			current = header.split(']').next().unwrap_or_default().trim();
+		} else if current == table && line.split('=').next().map(str::trim) == Some(key) {
+			return Some(i + 1);
+		}
+	}
+
+	None
+}
+
+/// The line of each number in the array `key` of `table`, in order.
+fn array_lines(source: &str, table: &str, key: &str) -> Vec<usize> {
+	let start = match line_of(source, table, key) {
+		Some(l) => l,
+		None => return Vec::new(),
+	};
+	let mut lines = Vec::new();
+
+	for (i, line) in source.lines().enumerate().skip(start - 1) {
+		let value = line.split('#').next().unwrap_or_default();
+		// Skip the key itself on the first line.
+		let value = value.split_once('=').map_or(value, |(_, v)| v);
+
+		for _ in value
+			.split(|c: char| !c.is_ascii_digit())
+			.filter(|n| !n.is_empty())
+		{
+			lines.push(i + 1);
+		}
+
+		if value.contains(']') {
+			break;
+		}
+	}
+
+	lines
+}
@@ -0,0 +1,147 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The directory the bot keeps its state in, between restarts.
+#[derive(Debug, Clone)]
+pub struct DataDir {
+	path: PathBuf,
+}
+
+impl DataDir {
+	pub fn new(path: impl AsRef<Path>) -> Self {
+		Self {
+			path: path.as_ref().to_path_buf(),
+		}
+	}
+
+	/// Creates the directory, and any missing parents, if it doesn't exist.
+	pub fn create(&self) -> Result<(), String> {
+		fs::create_dir_all(&self.path)
+			.map_err(|e| format!("Unable to create {}: {e}", self.path.display()))
+	}
+
+	/// The message cache, used to edit and delete mirrored messages.
+	pub fn cache(&self) -> PathBuf {
+		self.path.join(".cache")
+	}
+
+	pub fn bans(&self) -> PathBuf {
+		self.path.join(".bans")
+	}
+
+	pub fn mutes(&self) -> PathBuf {
+		self.path.join(".mutes")
+	}
+}
+
+/// `path` with `extension` appended, such as `.bans.bak`.
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+	let mut path = path.as_os_str().to_owned();
+	path.push(".");
+	path.push(extension);
+
+	PathBuf::from(path)
+}
+
+/// Reads the state file at `path`, written by [`write_state`].
+///
+/// If the file is corrupt it's moved aside to `<path>.corrupt` and the backup
+/// of the previous version is read instead. Returns `None` if there is
+/// nothing to read, so the caller can start empty.
+pub fn read_state<T: DeserializeOwned>(path: &Path) -> Option<T> {
+	let backup = with_extension(path, "bak");
+
+	match read_json(path) {
+		Ok(Some(state)) => return Some(state),
+		// A crash between the two renames in `write_state` leaves only the backup.
+		Ok(None) if !backup.exists() => return None,
+		Ok(None) => println!("{} is missing, trying the backup", path.display()),
+		Err(e) => {
+			let corrupt = with_extension(path, "corrupt");
+
+			println!(
+				"{} is corrupt ({e}), moving it to {} and trying the backup",
+				path.display(),
+				corrupt.display()
+			);
+
+			if let Err(e) = fs::rename(path, &corrupt) {
+				println!("Unable to move {}: {e}", path.display());
+			}
+		}
+	}
+
+	match read_json(&backup) {
+		Ok(Some(state)) => {
+			println!("Recovered {} from {}", path.display(), backup.display());
+			Some(state)
+		}
+		Ok(None) => {
+			println!("There is no backup of {}, starting empty", path.display());
+			None
+		}
+		Err(e) => {
+			println!(
+				"The backup {} is corrupt too ({e}), starting empty",
+				backup.display()
+			);
+			None
+		}
+	}
+}
+
+/// Reads and deserializes `path`, returning `None` if it doesn't exist or is
+/// empty.
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+	let buf = match fs::read_to_string(path) {
+		Ok(buf) => buf,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(e.to_string()),
+	};
+
+	if buf.trim().is_empty() {
+		return Ok(None);
+	}
+
+	serde_json::from_str(&buf)
+		.map(Some)
+		.map_err(|e| e.to_string())
+}
+
+/// Writes `state` to `path` without leaving it half written if the bot stops
+/// partway through. The state is written and synced to `<path>.tmp`, the
+/// current file becomes `<path>.bak`, and the new file is renamed into place.
+pub fn write_state<T: Serialize>(path: &Path, state: &T) -> Result<(), io::Error> {
+	let temporary = with_extension(path, "tmp");
+	let mut file = OpenOptions::new()
+		.write(true)
+		.create(true)
+		.truncate(true)
+		.open(&temporary)?;
+
+	file.write_all(serde_json::to_string(state)?.as_bytes())?;
+	file.sync_all()?;
+
+	if path.exists() {
+		fs::rename(path, with_extension(path, "bak"))?;
+	}
+
+	fs::rename(&temporary, path)?;
+
+	// Syncing the directory makes the renames durable, this isn't possible on
+	// every platform so errors are ignored.
+	if let Some(dir) = path.parent() {
+		let dir = if dir.as_os_str().is_empty() {
+			Path::new(".")
+		} else {
+			dir
+		};
+		let _ = File::open(dir).and_then(|d| d.sync_all());
+	}
+
+	Ok(())
+}
@@ -1,16 +1,23 @@
-use std::{
-	collections::HashMap,
-	fs::OpenOptions,
-	io::{self, Read, Write},
-	ops::Deref,
-};
+use std::{collections::HashMap, io, path::Path, time::Duration};
 
 use serde::{Deserialize, Serialize};
-use serenity::model::{
-	prelude::{GuildId, UserId},
-	Timestamp,
+use serenity::{
+	http::Http,
+	model::{
+		prelude::{GuildId, UserId},
+		Timestamp,
+	},
+	prelude::Mutex,
 };
 
+use crate::intergalactic_chat::data::{read_state, write_state};
+
+use super::mod_log::{ModLog, ModLogEvent};
+
+/// The origin of bans made with the `bans add` command, which aren't made in a
+/// server.
+pub const CLI_ORIGIN: GuildId = GuildId(0);
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BanList {
 	pub list: HashMap<UserId, BanEntry>,
@@ -22,6 +29,36 @@ pub struct BanEntry {
 	pub executor: UserId,
 	pub ban_origin: GuildId,
 	pub timestamp: Timestamp,
+	/// When the ban is lifted, if it isn't permanent.
+	#[serde(default)]
+	pub expires_at: Option<Timestamp>,
+}
+
+impl BanEntry {
+	pub fn is_expired(&self, now: Timestamp) -> bool {
+		match self.expires_at {
+			Some(e) => e.unix_timestamp() <= now.unix_timestamp(),
+			None => false,
+		}
+	}
+
+	/// Displays the server the ban was made in, or `CLI` for bans made with
+	/// `bans add`.
+	pub fn display_origin(&self) -> String {
+		if self.ban_origin == CLI_ORIGIN {
+			"CLI".to_owned()
+		} else {
+			self.ban_origin.to_string()
+		}
+	}
+
+	/// Displays when the ban expires as a Discord timestamp.
+	pub fn display_expiry(&self) -> String {
+		match self.expires_at {
+			Some(e) => format!("<t:{}:R>", e.unix_timestamp()),
+			None => "Never".to_owned(),
+		}
+	}
 }
 
 impl BanList {
@@ -31,38 +68,500 @@ impl BanList {
 		}
 	}
 
-	pub fn initialize(path: &str) -> Self {
-		let mut buf = String::new();
+	/// Reads the state saved at `path`, recovering from the backup if it's
+	/// corrupt and starting empty if there is nothing to read.
+	pub fn initialize(path: &Path) -> Self {
+		read_state(path).unwrap_or_else(Self::new)
+	}
 
-		OpenOptions::new()
-			.read(true)
-			.write(true)
-			.create(true)
-			.open(path)
-			.unwrap()
-			.read_to_string(&mut buf)
-			.unwrap();
+	/// Removes every ban that has expired by `now`, returning the removed bans.
+	pub fn remove_expired(&mut self, now: Timestamp) -> Vec<(UserId, BanEntry)> {
+		let expired: Vec<UserId> = self
+			.list
+			.iter()
+			.filter(|(_, e)| e.is_expired(now))
+			.map(|(u, _)| *u)
+			.collect();
 
-		if buf.len() == 0 {
-			Self::new()
-		} else {
-			serde_json::from_str::<Self>(buf.deref()).unwrap()
+		expired
+			.into_iter()
+			.filter_map(|u| self.list.remove_entry(&u))
+			.collect()
+	}
+
+	/// Converts the ban list to CSV, with one row for each ban. The file can be
+	/// imported again with [`parse_ban_import`].
+	pub fn to_csv(&self) -> String {
+		let mut csv = String::from("user,reason,executor,ban_origin,timestamp,expires_at\n");
+
+		for (user, entry) in &self.list {
+			csv.push_str(&format!(
+				"{},\"{}\",{},{},{},{}\n",
+				user,
+				entry.reason.replace('"', "\"\""),
+				entry.executor,
+				entry.display_origin(),
+				entry.timestamp,
+				entry.expires_at.map(|e| e.to_string()).unwrap_or_default()
+			));
 		}
+
+		csv
 	}
 
-	/// Writes [`BanList`] to the file provided by `path`. If that file does
-	/// not exist, a new one will be created.
-	pub fn write_to_file(self, path: &str) -> Result<Self, io::Error> {
-		OpenOptions::new()
-			.read(true)
-			.write(true)
-			.create(true)
-			.truncate(true)
-			.open(path)
-			.unwrap()
-			.write_all(serde_json::to_string(&self).unwrap().as_bytes())
-			.unwrap();
-
-		Ok(self)
+	/// Writes [`BanList`] to the file provided by `path`, keeping the previous
+	/// version as a backup.
+	pub fn write_to_file(&self, path: &Path) -> Result<(), io::Error> {
+		write_state(path, self)
+	}
+}
+
+/// A ban read from an import file, before it is added to the [`BanList`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedBan {
+	pub user: UserId,
+	#[serde(default)]
+	pub reason: String,
+	#[serde(default)]
+	pub expires_at: Option<Timestamp>,
+}
+
+/// Parses a file of bans to import. CSV files have a `user` column and
+/// optionally `reason` and `expires_at` columns, if there is no header row the
+/// first two columns are used as the user and reason. JSON files are either an
+/// array of [`ImportedBan`] or a file exported by `/network-bans export`.
+///
+/// Returns the bans that were read, and a description of every line that
+/// couldn't be.
+pub fn parse_ban_import(data: &str, csv: bool) -> (Vec<ImportedBan>, Vec<String>) {
+	if !csv {
+		if let Ok(list) = serde_json::from_str::<BanList>(data) {
+			let bans = list
+				.list
+				.into_iter()
+				.map(|(user, entry)| ImportedBan {
+					user,
+					reason: entry.reason,
+					expires_at: entry.expires_at,
+				})
+				.collect();
+
+			return (bans, Vec::new());
+		}
+
+		return match serde_json::from_str::<Vec<ImportedBan>>(data) {
+			Ok(bans) => (bans, Vec::new()),
+			Err(e) => (Vec::new(), vec![format!("Invalid JSON: {e}")]),
+		};
+	}
+
+	let mut bans = Vec::new();
+	let mut errors = Vec::new();
+	let mut lines = data
+		.lines()
+		.enumerate()
+		.filter(|(_, l)| !l.trim().is_empty())
+		.peekable();
+
+	// Columns of the user, reason and expiry.
+	let mut columns = (0, Some(1), None);
+
+	if let Some((_, header)) = lines.peek() {
+		let header = split_csv_line(header);
+
+		if header.first().is_some_and(|h| h.parse::<u64>().is_err()) {
+			let find = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+
+			columns = (
+				find("user").unwrap_or(0),
+				find("reason"),
+				find("expires_at"),
+			);
+			lines.next();
+		}
+	}
+
+	for (n, line) in lines {
+		let fields = split_csv_line(line);
+		let user = match fields.get(columns.0).and_then(|u| u.parse::<u64>().ok()) {
+			Some(u) if u != 0 => UserId(u),
+			_ => {
+				errors.push(format!("Line {}: invalid user ID", n + 1));
+				continue;
+			}
+		};
+		let field = |column: Option<usize>| {
+			column
+				.and_then(|c| fields.get(c))
+				.filter(|f| !f.is_empty())
+				.cloned()
+		};
+		let expires_at = match field(columns.2).map(|e| Timestamp::parse(&e)) {
+			Some(Ok(e)) => Some(e),
+			Some(Err(_)) => {
+				errors.push(format!("Line {}: invalid expiry", n + 1));
+				continue;
+			}
+			None => None,
+		};
+
+		bans.push(ImportedBan {
+			user,
+			reason: field(columns.1).unwrap_or_default(),
+			expires_at,
+		});
+	}
+
+	(bans, errors)
+}
+
+/// Splits a line of CSV into its fields, handling quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+	let mut fields = Vec::new();
+	let mut field = String::new();
+	let mut quoted = false;
+	let mut chars = line.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'"' if quoted && chars.peek() == Some(&'"') => {
+				field.push('"');
+				chars.next();
+			}
+			'"' => quoted = !quoted,
+			',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_owned()),
+			c => field.push(c),
+		}
+	}
+
+	fields.push(field.trim().to_owned());
+
+	fields
+}
+
+/// Lifts every ban that has expired by `now`, notifying the users by direct
+/// message and posting to the mod-log. The ban list is saved to `path` if any
+/// bans were lifted.
+pub async fn lift_expired_bans(
+	http: &Http, ban_list: &Mutex<BanList>, path: &Path, mod_log: &Mutex<ModLog>, now: Timestamp,
+) {
+	let expired = {
+		let mut ban_list = ban_list.lock().await;
+		let expired = ban_list.remove_expired(now);
+
+		if !expired.is_empty() {
+			if let Err(e) = ban_list.write_to_file(path) {
+				eprintln!("Unable to save the ban list: {e}");
+			}
+		}
+
+		expired
+	};
+
+	for (user, entry) in expired {
+		println!("The network ban for {user} has expired");
+
+		if let Ok(dm) = user.create_dm_channel(http).await {
+			dm.say(
+				http,
+				"Your network ban has expired. Your messages can now be sent to other servers.",
+			)
+			.await
+			.ok();
+		}
+
+		mod_log
+			.lock()
+			.await
+			.post_all(
+				http,
+				ModLogEvent::BanExpired {
+					user,
+					entry: &entry,
+				},
+			)
+			.await;
+	}
+}
+
+/// Parses a duration such as `30m`, `12h`, `7d` or `1w2d`. Supported units are
+/// seconds, minutes, hours, days and weeks.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+	let mut total: u64 = 0;
+	let mut number = String::new();
+
+	for c in input.trim().chars() {
+		if c.is_ascii_digit() {
+			number.push(c);
+			continue;
+		}
+
+		let unit = match c.to_ascii_lowercase() {
+			's' => 1,
+			'm' => 60,
+			'h' => 60 * 60,
+			'd' => 60 * 60 * 24,
+			'w' => 60 * 60 * 24 * 7,
+			_ => return None,
+		};
+
+		total = total.checked_add(number.parse::<u64>().ok()?.checked_mul(unit)?)?;
+		number.clear();
+	}
+
+	if !number.is_empty() || total == 0 {
+		return None;
+	}
+
+	Some(Duration::from_secs(total))
+}
+
+/// When a ban of `duration` starting at `now` expires, `None` if that is too far
+/// in the future to be represented.
+pub fn expiry_after(now: Timestamp, duration: Duration) -> Option<Timestamp> {
+	let seconds = i64::try_from(duration.as_secs()).ok()?;
+
+	Timestamp::from_unix_timestamp(now.unix_timestamp().checked_add(seconds)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A ban made at the Unix epoch, expiring `expires_in` seconds later.
+	fn ban(expires_in: Option<i64>) -> BanEntry {
+		BanEntry {
+			reason: "Spam".to_owned(),
+			executor: UserId(1),
+			ban_origin: GuildId(2),
+			timestamp: at(0),
+			expires_at: expires_in.map(at),
+		}
+	}
+
+	fn at(secs: i64) -> Timestamp {
+		Timestamp::from_unix_timestamp(secs).unwrap()
+	}
+
+	#[test]
+	fn bans_made_with_the_cli_show_it_as_their_origin() {
+		let mut entry = ban(None);
+		assert_eq!(entry.display_origin(), "2");
+
+		entry.ban_origin = CLI_ORIGIN;
+		assert_eq!(entry.display_origin(), "CLI");
+
+		let mut ban_list = BanList::new();
+		ban_list.list.insert(UserId(3), entry);
+		assert!(ban_list.to_csv().contains(",1,CLI,"));
+	}
+
+	#[test]
+	fn bans_expire_at_their_expiry() {
+		let entry = ban(Some(60));
+
+		assert!(!entry.is_expired(at(59)));
+		assert!(entry.is_expired(at(60)));
+		assert!(entry.is_expired(at(61)));
+	}
+
+	#[test]
+	fn permanent_bans_never_expire() {
+		assert!(!ban(None).is_expired(at(i32::MAX as i64)));
+	}
+
+	#[test]
+	fn only_expired_bans_are_removed() {
+		let mut list = BanList::new();
+		list.list.insert(UserId(10), ban(Some(60)));
+		list.list.insert(UserId(11), ban(Some(120)));
+		list.list.insert(UserId(12), ban(None));
+
+		assert!(list.remove_expired(at(59)).is_empty());
+
+		let expired = list.remove_expired(at(60));
+
+		assert_eq!(expired.len(), 1);
+		assert_eq!(expired[0].0, UserId(10));
+		assert_eq!(list.list.len(), 2);
+
+		let expired = list.remove_expired(at(1_000));
+
+		assert_eq!(expired.len(), 1);
+		assert_eq!(expired[0].0, UserId(11));
+		assert!(list.list.contains_key(&UserId(12)));
+		assert!(list.remove_expired(at(1_000)).is_empty());
+	}
+
+	/// The users, reasons and expiries of `bans`, sorted by user.
+	fn summary(bans: &[ImportedBan]) -> Vec<(u64, String, Option<i64>)> {
+		let mut summary: Vec<_> = bans
+			.iter()
+			.map(|b| {
+				(
+					b.user.0,
+					b.reason.to_owned(),
+					b.expires_at.map(|e| e.unix_timestamp()),
+				)
+			})
+			.collect();
+		summary.sort();
+
+		summary
+	}
+
+	#[test]
+	fn csv_with_a_header_uses_its_columns() {
+		let (bans, errors) = parse_ban_import(
+			"expires_at,User,reason\n1970-01-01T00:01:00Z,10,Spam\n,11,\n",
+			true,
+		);
+
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(
+			summary(&bans),
+			vec![(10, "Spam".to_owned(), Some(60)), (11, String::new(), None)]
+		);
+	}
+
+	#[test]
+	fn csv_without_a_header_is_user_then_reason() {
+		let (bans, errors) = parse_ban_import("10,Spam\n\n11\n", true);
+
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(
+			summary(&bans),
+			vec![(10, "Spam".to_owned(), None), (11, String::new(), None)]
+		);
+	}
+
+	#[test]
+	fn csv_fields_can_be_quoted() {
+		let (bans, errors) = parse_ban_import("user,reason\n10,\"Spam, and \"\"ads\"\"\"\n", true);
+
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(bans[0].reason, "Spam, and \"ads\"");
+	}
+
+	#[test]
+	fn invalid_csv_lines_are_reported_and_skipped() {
+		let (bans, errors) = parse_ban_import(
+			"user,reason,expires_at\nabc,Spam,\n0,Spam,\n11,Spam,tomorrow\n12,Spam,\n",
+			true,
+		);
+
+		assert_eq!(summary(&bans), vec![(12, "Spam".to_owned(), None)]);
+		assert_eq!(
+			errors,
+			vec![
+				"Line 2: invalid user ID",
+				"Line 3: invalid user ID",
+				"Line 4: invalid expiry",
+			]
+		);
+	}
+
+	#[test]
+	fn json_imports_accept_a_list_of_bans() {
+		let (bans, errors) = parse_ban_import(
+			r#"[{"user": "10", "reason": "Spam"}, {"user": "11"}]"#,
+			false,
+		);
+
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(
+			summary(&bans),
+			vec![(10, "Spam".to_owned(), None), (11, String::new(), None)]
+		);
+
+		let (bans, errors) = parse_ban_import("{", false);
+
+		assert!(bans.is_empty());
+		assert!(errors[0].starts_with("Invalid JSON"));
+	}
+
+	/// A ban list with a permanent ban and a timed ban with quotes and commas
+	/// in its reason.
+	fn exported() -> (BanList, Vec<(u64, String, Option<i64>)>) {
+		let mut list = BanList::new();
+		let mut timed = ban(Some(3_600));
+		timed.reason = "Said \"hi\", then spammed".to_owned();
+		list.list.insert(UserId(10), ban(None));
+		list.list.insert(UserId(11), timed);
+
+		let expected = vec![
+			(10, "Spam".to_owned(), None),
+			(11, "Said \"hi\", then spammed".to_owned(), Some(3_600)),
+		];
+
+		(list, expected)
+	}
+
+	#[test]
+	fn json_exports_can_be_imported() {
+		let (list, expected) = exported();
+		let (bans, errors) = parse_ban_import(&serde_json::to_string_pretty(&list).unwrap(), false);
+
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(summary(&bans), expected);
+	}
+
+	#[test]
+	fn csv_exports_can_be_imported() {
+		let (list, expected) = exported();
+		let (bans, errors) = parse_ban_import(&list.to_csv(), true);
+
+		assert!(errors.is_empty(), "{errors:?}");
+		assert_eq!(summary(&bans), expected);
+	}
+
+	#[test]
+	fn durations_are_parsed() {
+		assert_eq!(parse_duration("30m"), Some(Duration::from_secs(30 * 60)));
+		assert_eq!(
+			parse_duration(" 12H "),
+			Some(Duration::from_secs(12 * 60 * 60))
+		);
+		assert_eq!(
+			parse_duration("1w2d"),
+			Some(Duration::from_secs(9 * 24 * 60 * 60))
+		);
+	}
+
+	#[test]
+	fn invalid_durations_are_rejected() {
+		for input in [
+			"",
+			"0d",
+			"0w0d",
+			"5y",
+			"d",
+			"12",
+			"1d12",
+			"-1d",
+			"99999999999999999999s",
+			"18446744073709551615w",
+		] {
+			assert_eq!(parse_duration(input), None, "{input:?}");
+		}
+	}
+
+	#[test]
+	fn expiries_too_far_in_the_future_are_rejected() {
+		let now = Timestamp::from_unix_timestamp(1_000).unwrap();
+
+		assert_eq!(
+			expiry_after(now, Duration::from_secs(60)),
+			Some(Timestamp::from_unix_timestamp(1_060).unwrap())
+		);
+		assert_eq!(expiry_after(now, Duration::from_secs(u64::MAX)), None);
+		assert_eq!(
+			expiry_after(now, Duration::from_secs(i64::MAX as u64)),
+			None
+		);
+		assert_eq!(
+			expiry_after(now, parse_duration("9999999999999w").unwrap()),
+			None
+		);
 	}
 }
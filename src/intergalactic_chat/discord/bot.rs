@@ -1,33 +1,228 @@
-use std::ops::Deref;
-use std::process::exit;
-use std::str::from_utf8;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
 use crate::intergalactic_chat::discord::commands;
-use crate::intergalactic_chat::discord::util::{execute_message_for_webhook, get_link_webhook};
+use crate::intergalactic_chat::discord::util::linked_channels;
 use crate::Config;
-use rumqttc::{AsyncClient, Event, Incoming, QoS};
+use rumqttc::{AsyncClient, QoS};
 use serenity::async_trait;
+use serenity::http::Http;
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
 use serenity::model::id::ChannelId;
-use serenity::model::prelude::interaction::Interaction;
+use serenity::model::prelude::interaction::{Interaction, InteractionResponseType};
 use serenity::model::prelude::{GuildId, MessageId, MessageUpdateEvent};
 use serenity::model::webhook::Webhook;
 use serenity::prelude::*;
-use tokio::sync::broadcast;
-use tokio::{signal, task};
+use tokio::sync::mpsc::Receiver;
+use tokio::task;
 
 use super::bans::BanList;
-use super::cache::{CacheValue, MessageCache};
+use super::cache::MessageCache;
+use super::deletions::{self, MirrorDeletion};
+use super::edits::{self, MessageEdit};
+use super::filter::{ContentFilter, FilterVerdict};
+use super::link::Startup;
+use super::mod_log::{ModLog, ModLogEvent};
+use super::mutes::MuteList;
+use super::permissions::is_allowed;
+use super::rate_limit::{RateLimitResult, RateLimits};
+use super::registration::sync_commands;
+use super::status::LinkStatus;
+use super::webhooks::{ensure_webhooks, maintain, WebhookHealth};
+use crate::intergalactic_chat::data::DataDir;
+use crate::intergalactic_chat::metrics::{DropReason, LinkMetrics};
+use crate::intergalactic_chat::mqtt::dispatcher::Probes;
+use crate::intergalactic_chat::mqtt::presence::{Heartbeat, PeerDirectory};
+use crate::intergalactic_chat::mqtt::MqttStatus;
+
+/// Sent when a user tries to use a command their tier doesn't allow.
+const PERMISSION_DENIED_MESSAGE: &str =
+	"You don't have permission to use this command, use `/link-permissions` to see who can.";
 
 pub struct DiscordHandler {
 	pub mq_client: AsyncClient,
-	pub mq_event_receiver: broadcast::Receiver<Event>,
-	pub config: Config,
+	/// Messages received from other bots, see
+	/// [`Dispatcher`](crate::intergalactic_chat::mqtt::dispatcher::Dispatcher).
+	pub mq_chat: Mutex<Receiver<Message>>,
+	pub mq_edits: Mutex<Receiver<MessageEdit>>,
+	pub mq_deletions: Mutex<Receiver<MirrorDeletion>>,
+	pub mq_probes: Arc<Probes>,
+	pub config: Arc<RwLock<Config>>,
+	/// The file the config was read from, read again by
+	/// [`DiscordHandler::reload_config`].
+	pub config_path: String,
+	pub data_dir: DataDir,
 	pub message_cache: Arc<Mutex<MessageCache>>,
 	pub ban_list: Arc<Mutex<BanList>>,
+	pub content_filter: Arc<Mutex<ContentFilter>>,
+	pub rate_limits: Arc<Mutex<RateLimits>>,
+	pub mod_log: Arc<Mutex<ModLog>>,
+	pub mute_list: Arc<Mutex<MuteList>>,
+	pub mqtt_status: Arc<Mutex<MqttStatus>>,
+	pub link_status: Arc<Mutex<LinkStatus>>,
+	/// The webhooks used to post in each linked channel.
+	pub webhooks: Arc<Mutex<Vec<Webhook>>>,
+	pub webhook_health: Arc<WebhookHealth>,
+	/// This bot's heartbeat, `None` until the bot is ready.
+	pub heartbeat: Arc<Mutex<Option<Heartbeat>>>,
+	pub peer_directory: Arc<Mutex<PeerDirectory>>,
+	pub metrics: Arc<LinkMetrics>,
+	pub startup: Startup,
+}
+
+impl DiscordHandler {
+	/// Creates webhooks for newly linked channels and forgets those of unlinked
+	/// ones, then updates the mod-log, heartbeat and slash commands to match.
+	/// Channels whose webhook can't be created are degraded and listed in the
+	/// error, the others are still linked.
+	pub async fn sync_links(&self, http: &Http, bot_name: &str) -> Result<(), String> {
+		let (channels, log_channels, command_scope) = {
+			let config = self.config.read().await;
+
+			(
+				config.discord.channels.to_owned(),
+				config.discord.mod_log_channels.to_owned(),
+				config.discord.commands,
+			)
+		};
+		let mod_log = ModLog::resolve(http, &log_channels, &channels).await;
+		let linked_guilds: HashSet<GuildId> = channels
+			.iter()
+			.filter_map(|c| mod_log.guild_of(ChannelId::from(*c)))
+			.collect();
+		*self.mod_log.lock().await = mod_log;
+
+		let failures = ensure_webhooks(
+			http,
+			&self.webhooks,
+			&self.webhook_health,
+			&self.mod_log,
+			&channels,
+			&format!("Webhook for {bot_name}"),
+		)
+		.await;
+
+		let linked = linked_channels(&channels, http).await;
+
+		if let Some(heartbeat) = self.heartbeat.lock().await.as_mut() {
+			heartbeat.channels = linked;
+		}
+
+		let mut errors: Vec<String> = failures
+			.iter()
+			.map(|(c, e)| format!("Unable to set up a webhook in {c}: {e}"))
+			.collect();
+
+		if let Err(e) = sync_commands(http, command_scope, &linked_guilds).await {
+			errors.push(e);
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(errors.join("\n"))
+		}
+	}
+
+	/// Writes the ban list to disk. This is called after every change, so bans
+	/// aren't lost if the bot is killed.
+	pub async fn save_bans(&self) {
+		if let Err(e) = self
+			.ban_list
+			.lock()
+			.await
+			.write_to_file(&self.data_dir.bans())
+		{
+			eprintln!("Unable to save the ban list: {e}");
+		}
+	}
+
+	/// Writes the mute list to disk, after every change like the ban list.
+	pub async fn save_mutes(&self) {
+		if let Err(e) = self
+			.mute_list
+			.lock()
+			.await
+			.write_to_file(&self.data_dir.mutes())
+		{
+			eprintln!("Unable to save the mute list: {e}");
+		}
+	}
+
+	/// Reads the config file again and applies it. The `[mqtt]`, `[metrics]`
+	/// and `[admin]` sections and the token are only read at startup, so
+	/// changes to them are ignored until the bot is restarted.
+	pub async fn reload_config(&self, http: &Http) -> Result<(), String> {
+		let mut new_config = Config::load(&self.config_path)?;
+		let bot_name = match self.heartbeat.lock().await.as_ref() {
+			Some(h) => h.name.to_owned(),
+			None => return Err("The bot isn't ready yet".to_owned()),
+		};
+
+		let previous = {
+			let mut config = self.config.write().await;
+
+			new_config.mqtt = config.mqtt.to_owned();
+			new_config.metrics = config.metrics.to_owned();
+			new_config.admin = config.admin.to_owned();
+			new_config.discord.token = config.discord.token.to_owned();
+
+			std::mem::replace(&mut *config, new_config.to_owned())
+		};
+
+		if previous.filter.rules != new_config.filter.rules {
+			*self.content_filter.lock().await =
+				ContentFilter::initialize(&new_config.filter.rules)?;
+		}
+
+		*self.rate_limits.lock().await = RateLimits::new(&new_config.rate_limit);
+		self.message_cache
+			.lock()
+			.await
+			.configure(new_config.cache.size, new_config.cache.max_age());
+
+		self.sync_links(http, &bot_name).await
+	}
+}
+
+#[cfg(test)]
+impl DiscordHandler {
+	/// A handler with empty state, connected to MQTT through `mq_client` and
+	/// `routes`. Used by tests, so each new field only needs a default here.
+	pub fn for_tests(
+		config: Config, mq_client: AsyncClient,
+		routes: crate::intergalactic_chat::mqtt::dispatcher::Routes, data_dir: DataDir,
+		mqtt_status: Arc<Mutex<MqttStatus>>, metrics: Arc<LinkMetrics>,
+	) -> Self {
+		Self {
+			mq_client,
+			mq_chat: Mutex::new(routes.chat),
+			mq_edits: Mutex::new(routes.edits),
+			mq_deletions: Mutex::new(routes.deletions),
+			mq_probes: routes.probes,
+			config_path: String::new(),
+			data_dir,
+			message_cache: Arc::new(Mutex::new(MessageCache::new(100, None))),
+			ban_list: Arc::new(Mutex::new(BanList::new())),
+			content_filter: Arc::new(Mutex::new(ContentFilter::default())),
+			rate_limits: Arc::new(Mutex::new(RateLimits::new(&config.rate_limit))),
+			mod_log: Arc::new(Mutex::new(ModLog::default())),
+			mute_list: Arc::new(Mutex::new(MuteList::new())),
+			mqtt_status,
+			link_status: Arc::new(Mutex::new(LinkStatus::new())),
+			webhooks: Arc::new(Mutex::new(Vec::new())),
+			webhook_health: Arc::new(WebhookHealth::default()),
+			heartbeat: Arc::new(Mutex::new(None)),
+			peer_directory: Arc::new(Mutex::new(PeerDirectory::new(
+				std::time::Duration::from_secs(30),
+			))),
+			metrics,
+			startup: Startup::default(),
+			config: Arc::new(RwLock::new(config)),
+		}
+	}
 }
 
 #[async_trait]
@@ -36,57 +231,42 @@ impl EventHandler for DiscordHandler {
 		println!("Setting things up...");
 
 		let reg_wh_start = Instant::now();
-		let mut event_receiver = self.mq_event_receiver.resubscribe();
-		let mut webhooks: Vec<Webhook> = Vec::new();
-
-		for channel in &self.config.discord.channels {
-			webhooks.push(
-				get_link_webhook(
-					ChannelId::from(channel.to_owned()),
-					format!("Webhook for {}", ready.user.name).deref(),
-					&context,
-				)
-				.await,
-			);
+
+		*self.heartbeat.lock().await = Some(Heartbeat {
+			client_id: self.config.read().await.mqtt.client_id.to_owned(),
+			bot_id: ready.user.id.0,
+			name: ready.user.name.to_owned(),
+			version: env!("CARGO_PKG_VERSION").to_owned(),
+			channels: Vec::new(),
+			uptime: 0,
+		});
+
+		// The channels that failed are retried by `maintain`.
+		if let Err(e) = self.sync_links(&context.http, &ready.user.name).await {
+			println!("{e}");
 		}
 
 		println!(
-			"Setup {} webhooks in {:#?}...",
-			&webhooks.len(),
+			"Setup {} webhooks and the commands in {:#?}...",
+			self.webhooks.lock().await.len(),
 			reg_wh_start.elapsed()
 		);
 
-		let reg_cmd_start = Instant::now();
-		let mut command_names: Vec<String> = Vec::new();
-
-		for guild in &ready.guilds {
-			let guild_id = guild.id;
-
-			let commands =
-				GuildId::set_application_commands(&guild_id, &context.http, |commands| {
-					commands
-						.create_application_command(|command| commands::ping::register(command))
-						.create_application_command(|command| commands::about::register(command))
-						.create_application_command(|command| {
-							commands::network_ban::register(command)
-						})
-						.create_application_command(|command| {
-							commands::network_unban::register(command)
-						})
-				})
-				.await
-				.unwrap();
-
-			for command in commands {
-				command_names.push(command.name);
-			}
+		// Serenity sends a ready event every time it reconnects to the gateway,
+		// only the webhooks and heartbeat are refreshed after the first.
+		if !self.startup.ready() {
+			println!("Reconnected to Discord as {}\n", ready.user.name);
+			return;
 		}
 
-		println!(
-			"Setup {:#?} commands in {:#?}...\n",
-			command_names.len(),
-			reg_cmd_start.elapsed()
-		);
+		task::spawn(maintain(
+			Arc::clone(&context.http),
+			Arc::clone(&self.config),
+			Arc::clone(&self.webhooks),
+			Arc::clone(&self.webhook_health),
+			Arc::clone(&self.mod_log),
+			ready.user.name.to_owned(),
+		));
 
 		println!(
 			"{} connected to Discord and ready to start receiving events!",
@@ -95,160 +275,234 @@ impl EventHandler for DiscordHandler {
 		println!("Invite with: https://discord.com/api/oauth2/authorize?client_id={}&permissions=1789592463424&scope=bot", ready.application.id);
 		println!(
 			"Watching {} channels in {} servers\n",
-			&self.config.discord.channels.len(),
+			self.config.read().await.discord.channels.len(),
 			ready.guilds.len()
 		);
-
-		let message_cache = Arc::clone(&self.message_cache);
-		let ban_list = Arc::clone(&self.ban_list);
-
-		task::spawn(async move {
-			loop {
-				match signal::ctrl_c().await {
-					Ok(()) => {
-						Arc::clone(&message_cache)
-							.lock()
-							.await
-							.to_owned()
-							.write_to_file(".cache")
-							.unwrap();
-
-						Arc::clone(&ban_list)
-							.lock()
-							.await
-							.to_owned()
-							.write_to_file(".bans")
-							.unwrap();
-
-						println!("Goodbye!");
-
-						exit(0)
-					}
-					Err(e) => {
-						eprintln!("Unable to listen for shutdown signal: {e}");
-					}
-				}
-			}
-		});
-
-		// Process the event and ensure it's a valid Discord message. The loop
-		// will simply return if a problem is found, as none of the issues are
-		// unrecoverable.
-		// TODO: This can definitely be done more efficiently!
-		loop {
-			let message = match event_receiver.recv().await {
-				Ok(Event::Incoming(Incoming::Publish(p))) => match from_utf8(&p.payload) {
-					Ok(p) => match serde_json::from_str::<Message>(p) {
-						Ok(p) => p,
-						_ => continue,
-					},
-					_ => continue,
-				},
-				_ => continue,
-			};
-
-			self.message_cache.lock().await.push(message.id, Vec::new());
-
-			for webhook in &webhooks {
-				let message = message.to_owned();
-				let context = context.to_owned();
-				let webhook = webhook.to_owned();
-				let message_cache = Arc::clone(&self.message_cache);
-				let message_id = message.id;
-
-				task::spawn(async move {
-					let m = execute_message_for_webhook(message, &context, &webhook).await;
-
-					match m {
-						Ok(m) => match m {
-							Some(m) => {
-								message_cache.lock().await.push_into_value(
-									message_id,
-									CacheValue {
-										related_channel_id: m.channel_id,
-										related_message_id: m.id,
-										related_webhook_id: webhook.id,
-									},
-								);
-							}
-							None => (),
-						},
-						Err(e) => println!("Error sending message {e}"),
-					}
-				});
-			}
-		}
 	}
 
 	async fn message_delete(
-		&self, context: Context, _: ChannelId, deleted_message_id: MessageId, _: Option<GuildId>,
+		&self, context: Context, channel_id: ChannelId, deleted_message_id: MessageId,
+		guild_id: Option<GuildId>,
 	) {
-		let c = &mut self.message_cache.lock().await;
-		let cache_value = c.get_entry(&deleted_message_id);
+		if deletions::mirror_deleted(self, deleted_message_id, guild_id).await {
+			self.metrics.observe_cache(true);
+			return;
+		}
 
-		let messages = match cache_value {
-			Some(v) => v,
-			None => return,
+		// Removing the entry first means the deletions below aren't handled as
+		// moderators deleting mirrors when Discord reports them.
+		let mirrors = {
+			let mut message_cache = self.message_cache.lock().await;
+			let mirrors = message_cache
+				.get_entry(&deleted_message_id)
+				.map(|(_, v)| v.to_owned());
+			self.metrics.observe_cache(mirrors.is_some());
+			message_cache.remove(&deleted_message_id);
+
+			match mirrors {
+				Some(v) => v,
+				None => return,
+			}
 		};
 
-		for i in messages.1 {
-			i.related_channel_id
+		for i in mirrors {
+			if let Err(e) = i
+				.related_channel_id
 				.delete_message(&context, i.related_message_id)
 				.await
-				.expect("Error deleting discord message");
-		}
+			{
+				println!("Error deleting the mirror {}: {e}", i.related_message_id);
+				self.webhook_health
+					.report(
+						&context.http,
+						&self.webhooks,
+						&self.mod_log,
+						i.related_channel_id,
+						i.related_webhook_id,
+						&e,
+					)
+					.await;
+				continue;
+			}
 
-		c.remove(&deleted_message_id);
+			let mod_log = self.mod_log.lock().await;
+			let mirror_guild = mod_log.guild_of(i.related_channel_id);
+
+			if mirror_guild != guild_id {
+				mod_log
+					.post(
+						&context,
+						mirror_guild,
+						ModLogEvent::MirrorDeleted {
+							origin_channel: channel_id,
+							origin_guild: guild_id,
+							origin_message: deleted_message_id,
+							mirror_channel: i.related_channel_id,
+						},
+					)
+					.await;
+			}
+		}
 	}
 
 	async fn message_update(&self, context: Context, new_data: MessageUpdateEvent) {
-		let new_content = match new_data.content {
+		let mut new_content = match new_data.content {
 			Some(v) => v,
 			None => return,
 		};
 
-		let c = &self.message_cache.lock().await;
-		let cache_value = c.get_entry(&new_data.id);
+		// Mirrors are edited through the webhook, which Discord reports too.
+		if new_data.author.as_ref().is_some_and(|a| a.bot)
+			|| !self
+				.config
+				.read()
+				.await
+				.discord
+				.channels
+				.contains(new_data.channel_id.as_u64())
+		{
+			return;
+		}
 
-		let messages = match cache_value {
-			Some(v) => v,
-			None => return,
-		};
+		if let Some(author) = &new_data.author {
+			if self.ban_list.lock().await.list.contains_key(&author.id) {
+				self.metrics.dropped(DropReason::Banned);
+				return;
+			}
+		}
 
-		for i in messages.1 {
-			Webhook::from_id(&context, i.related_webhook_id)
-				.await
-				.expect("Error getting webhook from id")
-				.edit_message(&context, i.related_message_id, |m| {
-					m.content(new_content.clone())
-				})
+		// Checked like a new message, so rules can't be avoided by editing a
+		// message after it was sent.
+		let result = self.content_filter.lock().await.check(&new_content);
+
+		for hit in &result.hits {
+			self.mod_log
+				.lock()
 				.await
-				.expect("Error editing webhook message");
+				.post(
+					&context,
+					new_data.guild_id,
+					ModLogEvent::FilterHit {
+						author: new_data.author.as_ref(),
+						channel: new_data.channel_id,
+						guild: new_data.guild_id,
+						message: new_data.id,
+						hit,
+						received: false,
+						edited: true,
+					},
+				)
+				.await;
 		}
+
+		match result.verdict {
+			FilterVerdict::Drop => {
+				self.metrics.dropped(DropReason::Filtered);
+				return;
+			}
+			FilterVerdict::Redact(content) => new_content = content,
+			FilterVerdict::Pass => (),
+		}
+
+		edits::publish(
+			self,
+			&MessageEdit {
+				message: new_data.id,
+				channel: new_data.channel_id,
+				guild: new_data.guild_id,
+				content: new_content,
+			},
+		)
+		.await;
 	}
 
-	async fn message(&self, _: Context, message: Message) {
+	async fn message(&self, context: Context, mut message: Message) {
 		// TODO: Look into a solution that doesn't ignore bots.
 		// TODO: Make this more efficient maybe?
-		if !self
-			.config
-			.discord
-			.channels
-			.contains(message.channel_id.as_u64())
-		{
-			return;
-		} else if message.author.bot {
+		let (linked, topic) = {
+			let config = self.config.read().await;
+
+			(
+				config
+					.discord
+					.channels
+					.contains(message.channel_id.as_u64()),
+				config.mqtt.topic.to_owned(),
+			)
+		};
+
+		if !linked || message.author.bot {
 			return;
-		} else if self
+		}
+
+		if self
 			.ban_list
 			.lock()
 			.await
 			.list
 			.contains_key(&message.author.id)
 		{
+			self.metrics.dropped(DropReason::Banned);
 			return;
 		}
 
+		let limited = self
+			.rate_limits
+			.lock()
+			.await
+			.check_sent(message.author.id, message.channel_id);
+
+		if let RateLimitResult::Limited { first } = limited {
+			if first {
+				message
+					.author
+					.dm(&context, |dm| {
+						dm.content(format!("Messages are being sent too quickly in <#{}>, so some of yours were not sent to other servers. Please slow down!", message.channel_id))
+					})
+					.await
+					.ok();
+
+				self.mod_log
+					.lock()
+					.await
+					.post(
+						&context,
+						message.guild_id,
+						ModLogEvent::RateLimited {
+							user: message.author.id,
+							channel: message.channel_id,
+						},
+					)
+					.await;
+			}
+
+			self.metrics.dropped(DropReason::RateLimited);
+			return;
+		}
+
+		let result = self.content_filter.lock().await.check(&message.content);
+
+		for hit in &result.hits {
+			self.mod_log
+				.lock()
+				.await
+				.post(
+					&context,
+					message.guild_id,
+					ModLogEvent::filter_hit(&message, hit, false),
+				)
+				.await;
+		}
+
+		match result.verdict {
+			FilterVerdict::Drop => {
+				self.metrics.dropped(DropReason::Filtered);
+				return;
+			}
+			FilterVerdict::Redact(content) => message.content = content,
+			FilterVerdict::Pass => (),
+		}
+
 		let mq_client = &self.mq_client;
 		let message_json = match serde_json::to_string(&message) {
 			Ok(json) => json,
@@ -258,37 +512,116 @@ impl EventHandler for DiscordHandler {
 		// TODO: This function currently just serializes and sends the entire Message
 		// as JSON, which can be optimized by removing unused fields.
 		mq_client
-			.publish(
-				&self.config.mqtt.topic,
-				QoS::ExactlyOnce,
-				false,
-				message_json,
-			)
+			.publish(&topic, QoS::ExactlyOnce, false, message_json)
 			.await
 			.ok();
+
+		LinkMetrics::increment(&self.metrics.messages_published);
 	}
 
 	async fn interaction_create(&self, context: Context, interaction: Interaction) {
-		if let Interaction::ApplicationCommand(command) = interaction {
-			match command.data.name.as_str() {
-				"ping" => commands::ping::run(&command.data.options, &command, &context)
+		let permissions = self.config.read().await.permissions.to_owned();
+
+		match interaction {
+			// Permissions set when registering commands can be changed by every
+			// server's admins, so the network's own permissions are checked here.
+			Interaction::ApplicationCommand(command)
+				if !is_allowed(
+					&permissions,
+					&command.data.name,
+					command.user.id,
+					command.member.as_ref(),
+				) =>
+			{
+				command
+					.create_interaction_response(&context.http, |r| {
+						r.kind(InteractionResponseType::ChannelMessageWithSource);
+						r.interaction_response_data(|rd| {
+							rd.content(PERMISSION_DENIED_MESSAGE);
+							rd.ephemeral(true)
+						})
+					})
+					.await
+					.unwrap()
+			}
+			Interaction::ApplicationCommand(command) => match command.data.name.as_str() {
+				"ping" => commands::ping::run(&command.data.options, &command, &context, self)
 					.await
 					.unwrap(),
 				"about" => commands::about::run(&command.data.options, &command, &context)
 					.await
 					.unwrap(),
 				"network-ban" => {
-					commands::network_ban::run(&command.data.options, &command, &context, &self)
+					commands::network_ban::run(&command.data.options, &command, &context, self)
 						.await
 						.unwrap()
 				}
 				"network-unban" => {
-					commands::network_unban::run(&command.data.options, &command, &context, &self)
+					commands::network_unban::run(&command.data.options, &command, &context, self)
+						.await
+						.unwrap()
+				}
+				"network-bans" => {
+					commands::network_bans::run(&command.data.options, &command, &context, self)
+						.await
+						.unwrap()
+				}
+				"link-mute" => {
+					commands::link_mute::run(&command.data.options, &command, &context, self)
+						.await
+						.unwrap()
+				}
+				"link-unmute" => {
+					commands::link_unmute::run(&command.data.options, &command, &context, self)
+						.await
+						.unwrap()
+				}
+				"link-status" => {
+					commands::link_status::run(&command.data.options, &command, &context, self)
+						.await
+						.unwrap()
+				}
+				"link-peers" => {
+					commands::link_peers::run(&command.data.options, &command, &context, self)
+						.await
+						.unwrap()
+				}
+				"link-permissions" => {
+					commands::link_permissions::run(&command.data.options, &command, &context, self)
 						.await
 						.unwrap()
 				}
 				_ => panic!("TODO: Unhandled command"),
-			};
+			},
+			Interaction::MessageComponent(component)
+				if component
+					.data
+					.custom_id
+					.starts_with(commands::network_bans::PAGE_BUTTON_PREFIX) =>
+			{
+				if is_allowed(
+					&permissions,
+					"network-bans",
+					component.user.id,
+					component.member.as_ref(),
+				) {
+					commands::network_bans::run_page(&component, &context, self)
+						.await
+						.unwrap()
+				} else {
+					component
+						.create_interaction_response(&context.http, |r| {
+							r.kind(InteractionResponseType::ChannelMessageWithSource);
+							r.interaction_response_data(|rd| {
+								rd.content(PERMISSION_DENIED_MESSAGE);
+								rd.ephemeral(true)
+							})
+						})
+						.await
+						.unwrap()
+				}
+			}
+			_ => (),
 		}
 	}
 }
@@ -1,12 +1,17 @@
 use std::{
-	collections::HashMap,
-	fs::OpenOptions,
-	io::{self, Read, Write},
-	ops::Deref,
+	collections::{HashMap, VecDeque},
+	io,
+	path::Path,
+	time::Duration,
 };
 
 use serde::{Deserialize, Serialize};
-use serenity::model::prelude::{ChannelId, MessageId, WebhookId};
+use serenity::model::{
+	prelude::{ChannelId, MessageId, WebhookId},
+	Timestamp,
+};
+
+use crate::intergalactic_chat::data::{read_state, write_state};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MessageCache {
@@ -17,6 +22,23 @@ pub struct MessageCache {
 	/// The key represents the original message sent by the user and the value
 	/// contains data related to messages the bot has posted.
 	cache: HashMap<MessageId, Vec<CacheValue>>,
+	/// The keys of `cache` in the order they were pushed, oldest first, used to
+	/// decide which entries to remove when the cache is full.
+	#[serde(default)]
+	order: VecDeque<MessageId>,
+	/// The channel each key was originally sent in.
+	#[serde(default)]
+	channels: HashMap<MessageId, ChannelId>,
+	/// The key each mirrored message is a copy of, rebuilt from `cache` when
+	/// the cache is loaded.
+	#[serde(skip)]
+	mirrors: HashMap<MessageId, MessageId>,
+	/// Entries for messages older than this are removed, if set.
+	#[serde(skip)]
+	max_age: Option<Duration>,
+	/// Whether the cache has changed since it was last written to disk.
+	#[serde(skip)]
+	dirty: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,97 +49,343 @@ pub struct CacheValue {
 }
 
 impl MessageCache {
-	pub fn new(size: usize) -> Self {
+	pub fn new(size: usize, max_age: Option<Duration>) -> Self {
 		Self {
 			size,
 			cache: HashMap::new(),
+			order: VecDeque::new(),
+			channels: HashMap::new(),
+			mirrors: HashMap::new(),
+			max_age,
+			dirty: false,
 		}
 	}
 
-	/// Pushes the value into the cache and removes one value if the cache is too
+	/// Pushes the value of `k`, sent in `channel`, into the cache, replacing
+	/// any value of `k`, and removes the oldest values if the cache is too
 	/// large.
-	pub fn push(&mut self, k: MessageId, v: Vec<CacheValue>) -> &mut Self {
-		self.pop_if_oversized();
+	pub fn push(&mut self, k: MessageId, channel: ChannelId, v: Vec<CacheValue>) -> &mut Self {
+		self.remove(&k);
+
+		for value in &v {
+			self.mirrors.insert(value.related_message_id, k);
+		}
+
 		self.cache.insert(k, v);
+		self.channels.insert(k, channel);
+		self.order.push_back(k);
+		self.dirty = true;
+		self.evict(Timestamp::now());
 
 		self
 	}
 
-	/// Pushes into the cache value by `k`.
+	/// Pushes into the cache value by `k`, if it's still cached. This never
+	/// removes anything from the cache.
 	pub fn push_into_value(&mut self, k: MessageId, v: CacheValue) -> &mut Self {
-		self.pop_if_oversized();
-		self.cache.entry(k).and_modify(|e| e.push(v));
+		if let Some(e) = self.cache.get_mut(&k) {
+			self.mirrors.insert(v.related_message_id, k);
+			e.push(v);
+			self.dirty = true;
+		}
 
 		self
 	}
 
-	pub fn initialize(path: &str, size: usize) -> Self {
-		let mut buf = String::new();
-
-		OpenOptions::new()
-			.read(true)
-			.write(true)
-			.create(true)
-			.open(path)
-			.unwrap()
-			.read_to_string(&mut buf)
-			.unwrap();
+	/// Reads the state saved at `path`, recovering from the backup if it's
+	/// corrupt and starting empty if there is nothing to read. The saved cache
+	/// is resized to `size`.
+	pub fn initialize(path: &Path, size: usize, max_age: Option<Duration>) -> Self {
+		let mut cache = match read_state::<Self>(path) {
+			Some(c) => c,
+			None => return Self::new(size, max_age),
+		};
 
-		if buf.len() == 0 {
-			Self::new(size)
-		} else {
-			serde_json::from_str::<Self>(buf.deref()).unwrap()
+		// Caches saved before the order was kept, or edited by hand, are ordered
+		// by when each message was sent.
+		if cache.order.len() != cache.cache.len()
+			|| cache.order.iter().any(|k| !cache.cache.contains_key(k))
+		{
+			cache.order = cache.cache.keys().copied().collect();
+			cache.order.make_contiguous().sort();
 		}
+
+		cache.mirrors = cache
+			.cache
+			.iter()
+			.flat_map(|(k, v)| v.iter().map(|m| (m.related_message_id, *k)))
+			.collect();
+
+		cache.configure(size, max_age);
+
+		cache
 	}
 
-	/// Writes [`MessageCache`] to the file provided by `path`. If that file does
-	/// not exist, a new one will be created.
-	pub fn write_to_file(self, path: &str) -> Result<Self, io::Error> {
-		OpenOptions::new()
-			.read(true)
-			.write(true)
-			.create(true)
-			.truncate(true)
-			.open(path)
-			.unwrap()
-			.write_all(serde_json::to_string(&self).unwrap().as_bytes())
-			.unwrap();
+	/// Changes the size and maximum age of the cache, removing the entries that
+	/// no longer fit.
+	pub fn configure(&mut self, size: usize, max_age: Option<Duration>) -> &mut Self {
+		self.size = size;
+		self.max_age = max_age;
+		self.evict(Timestamp::now());
 
-		Ok(self)
+		self
+	}
+
+	/// Writes [`MessageCache`] to the file provided by `path`, keeping the previous
+	/// version as a backup.
+	pub fn write_to_file(&mut self, path: &Path) -> Result<(), io::Error> {
+		write_state(path, self)?;
+		self.dirty = false;
+
+		Ok(())
+	}
+
+	/// Writes the cache to `path` if it has changed since it was last written,
+	/// returning whether it was written.
+	pub fn snapshot(&mut self, path: &Path) -> Result<bool, io::Error> {
+		if !self.dirty {
+			return Ok(false);
+		}
+
+		self.write_to_file(path).map(|_| true)
 	}
 
 	pub fn get_entry(&self, k: &MessageId) -> Option<(&MessageId, &Vec<CacheValue>)> {
 		self.cache.get_key_value(k)
 	}
 
+	/// The key `mirror` is a copy of, and the channel it was sent in if known.
+	pub fn origin_of(&self, mirror: &MessageId) -> Option<(MessageId, Option<ChannelId>)> {
+		self.mirrors
+			.get(mirror)
+			.map(|k| (*k, self.channels.get(k).copied()))
+	}
+
+	/// Forgets a single mirrored message, keeping the rest of its entry.
+	/// Returns the key it was a copy of.
+	pub fn remove_mirror(&mut self, mirror: &MessageId) -> Option<MessageId> {
+		let k = self.mirrors.remove(mirror)?;
+
+		if let Some(e) = self.cache.get_mut(&k) {
+			e.retain(|v| v.related_message_id != *mirror);
+			self.dirty = true;
+		}
+
+		Some(k)
+	}
+
+	/// The number of entries in the cache.
+	pub fn len(&self) -> usize {
+		self.cache.len()
+	}
+
+	/// The maximum number of entries the cache can hold.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
 	pub fn remove(&mut self, k: &MessageId) -> &mut Self {
-		self.cache.remove(k);
+		if let Some(v) = self.cache.remove(k) {
+			for value in v {
+				self.mirrors.remove(&value.related_message_id);
+			}
+
+			self.channels.remove(k);
+			self.order.retain(|o| o != k);
+			self.dirty = true;
+		}
 
 		self
 	}
 
-	/// Checks that the cache is not larger than than `size`. If it is larger than
-	/// `size` than the adequate number of items are removed as to make the cache
-	/// exactly equal to `size`.
-	fn pop_if_oversized(&mut self) -> &mut Self {
-		// If the difference is greater than zero, the cache is too big
-		let z: i32 = self.size.try_into().unwrap();
-		let l: i32 = self.cache.len().try_into().unwrap();
-		let diff: i32 = l - z;
-
-		if diff > 0 {
-			let keys: Vec<_> = {
-				self.cache
-					.keys()
-					.take(diff.unsigned_abs().try_into().unwrap())
-					.copied()
-					.collect()
-			};
-			for k in keys {
-				self.cache.remove(&k);
+	/// Removes entries older than `max_age`, then the oldest entries until the
+	/// cache is no larger than `size`.
+	fn evict(&mut self, now: Timestamp) {
+		if let Some(max_age) = self.max_age {
+			let oldest =
+				now.unix_timestamp() - i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX);
+			let expired: Vec<_> = self
+				.order
+				.iter()
+				.filter(|k| k.created_at().unix_timestamp() < oldest)
+				.copied()
+				.collect();
+
+			for k in expired {
+				self.remove(&k);
 			}
 		}
 
-		self
+		while self.cache.len() > self.size {
+			match self.order.front().copied() {
+				Some(k) => {
+					self.remove(&k);
+				}
+				None => break,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rand::rngs::StdRng;
+	use rand::{Rng, SeedableRng};
+
+	use super::*;
+
+	/// Milliseconds between the Unix epoch and the Discord epoch.
+	const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+	/// A message ID for a message sent at `secs` after the Unix epoch.
+	fn id_at(secs: u64, increment: u64) -> MessageId {
+		MessageId(((secs * 1000 - DISCORD_EPOCH) << 22) | increment)
+	}
+
+	fn value(n: u64) -> CacheValue {
+		CacheValue {
+			related_channel_id: ChannelId(n),
+			related_message_id: MessageId(n),
+			related_webhook_id: WebhookId(n),
+		}
+	}
+
+	/// Runs random operations against caches of random sizes, checking the
+	/// eviction invariants after each one.
+	#[test]
+	fn eviction_invariants_hold() {
+		for seed in 0..200 {
+			let mut rng = StdRng::seed_from_u64(seed);
+			let size = rng.gen_range(0..20);
+			let mut cache = MessageCache::new(size, None);
+			// The keys that should be cached, oldest first.
+			let mut pushed: Vec<MessageId> = Vec::new();
+
+			for step in 0..300 {
+				let op = rng.gen_range(0..10);
+
+				if op < 6 || pushed.is_empty() {
+					let k = id_at(1_700_000_000 + step, seed);
+
+					cache.push(k, ChannelId(1), Vec::new());
+					pushed.retain(|p| *p != k);
+					pushed.push(k);
+
+					if pushed.len() > size {
+						pushed.remove(0);
+					}
+
+					// The newest entry is never the one removed.
+					if size > 0 {
+						assert!(cache.get_entry(&k).is_some(), "seed {seed}");
+					}
+				} else if op < 8 {
+					let k = pushed[rng.gen_range(0..pushed.len())];
+					let len = cache.len();
+					let had = cache.get_entry(&k).map(|(_, v)| v.len());
+
+					cache.push_into_value(k, value(step));
+
+					// Extending an entry never removes anything.
+					assert_eq!(cache.len(), len, "seed {seed}");
+					assert_eq!(
+						cache.get_entry(&k).map(|(_, v)| v.len()),
+						had.map(|l| l + 1),
+						"seed {seed}"
+					);
+				} else if op < 9 {
+					let mirror = cache.mirrors.keys().next().copied();
+
+					if let Some(mirror) = mirror {
+						let (k, _) = cache.origin_of(&mirror).unwrap();
+						let len = cache.get_entry(&k).unwrap().1.len();
+
+						// Forgetting a mirror keeps the rest of its entry.
+						assert_eq!(cache.remove_mirror(&mirror), Some(k), "seed {seed}");
+						assert_eq!(cache.get_entry(&k).unwrap().1.len(), len - 1, "seed {seed}");
+						assert!(cache.origin_of(&mirror).is_none(), "seed {seed}");
+					}
+				} else {
+					let k = pushed.remove(rng.gen_range(0..pushed.len()));
+
+					cache.remove(&k);
+					assert!(cache.get_entry(&k).is_none(), "seed {seed}");
+				}
+
+				// Every cached mirror leads back to its entry, and nothing else does.
+				let mirrors: Vec<_> = cache
+					.cache
+					.iter()
+					.flat_map(|(k, v)| v.iter().map(move |m| (m.related_message_id, *k)))
+					.collect();
+
+				assert_eq!(cache.mirrors.len(), mirrors.len(), "seed {seed}");
+				assert!(
+					mirrors
+						.iter()
+						.all(|(m, k)| cache.origin_of(m) == Some((*k, Some(ChannelId(1))))),
+					"seed {seed}"
+				);
+
+				// Only the oldest entries are ever removed to make space.
+				assert!(cache.len() <= size, "seed {seed}");
+				assert_eq!(cache.len(), pushed.len(), "seed {seed}");
+				assert_eq!(cache.order, pushed, "seed {seed}");
+				assert!(
+					pushed.iter().all(|k| cache.get_entry(k).is_some()),
+					"seed {seed}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn old_entries_expire() {
+		let now = Timestamp::from_unix_timestamp(1_700_000_000).unwrap();
+		let max_age = Duration::from_secs(3600);
+
+		for seed in 0..50 {
+			let mut rng = StdRng::seed_from_u64(seed);
+			let mut cache = MessageCache::new(1000, None);
+			let ages: Vec<u64> = (0..100).map(|_| rng.gen_range(0..7200)).collect();
+
+			for (i, age) in ages.iter().enumerate() {
+				cache.push(
+					id_at(1_700_000_000 - age, i as u64),
+					ChannelId(1),
+					Vec::new(),
+				);
+			}
+
+			cache.max_age = Some(max_age);
+			cache.evict(now);
+
+			for (i, age) in ages.iter().enumerate() {
+				let k = id_at(1_700_000_000 - age, i as u64);
+
+				assert_eq!(
+					cache.get_entry(&k).is_some(),
+					*age <= max_age.as_secs(),
+					"seed {seed}, age {age}"
+				);
+			}
+
+			assert_eq!(cache.order.len(), cache.len(), "seed {seed}");
+		}
+	}
+
+	#[test]
+	fn shrinking_keeps_the_newest_entries() {
+		let mut cache = MessageCache::new(10, None);
+		let keys: Vec<_> = (0..10).map(|i| id_at(1_700_000_000 + i, 0)).collect();
+
+		for k in &keys {
+			cache.push(*k, ChannelId(1), Vec::new());
+		}
+
+		cache.configure(3, None);
+
+		assert_eq!(cache.len(), 3);
+		assert!(keys[7..].iter().all(|k| cache.get_entry(k).is_some()));
 	}
 }
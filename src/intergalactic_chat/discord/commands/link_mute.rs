@@ -0,0 +1,235 @@
+use std::collections::hash_map::Entry;
+
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::GuildId;
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+use serenity::utils::Colour;
+
+use super::{find_option, guild_and_executor, respond_guild_only};
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::mod_log::ModLogEvent;
+use crate::intergalactic_chat::discord::mutes::MuteEntry;
+
+pub async fn run(
+	options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let subcommand = options.first().expect("Expected subcommand");
+	let (guild_id, executor) = match guild_and_executor(command) {
+		Some(v) => v,
+		None => return respond_guild_only(command, context).await,
+	};
+	let reason = match find_option(&subcommand.options, "reason") {
+		Some(CommandDataOptionValue::String(reason)) => reason.to_owned(),
+		_ => "No reason provided".to_owned(),
+	};
+	let entry = MuteEntry {
+		reason,
+		executor,
+		timestamp: command.id.created_at(),
+	};
+
+	let content = match subcommand.name.as_str() {
+		"user" => match find_option(&subcommand.options, "user") {
+			Some(CommandDataOptionValue::User(user, _)) => {
+				let muted = {
+					let mut mute_list = handler.mute_list.lock().await;
+					let users = &mut mute_list.guild(guild_id).users;
+
+					// The existing mute is kept, rather than replacing its reason.
+					match users.entry(user.id) {
+						Entry::Occupied(_) => false,
+						Entry::Vacant(v) => {
+							v.insert(entry.to_owned());
+							true
+						}
+					}
+				};
+
+				if !muted {
+					"This user is already muted on this server.".to_owned()
+				} else {
+					handler.save_mutes().await;
+					handler
+						.mod_log
+						.lock()
+						.await
+						.post(
+							context,
+							Some(guild_id),
+							ModLogEvent::Muted {
+								target: format!("<@{}>", user.id),
+								entry: &entry,
+							},
+						)
+						.await;
+
+					format!(
+						"Muted <@{}>, their messages from other servers will no longer be shown here.",
+						user.id
+					)
+				}
+			}
+			_ => "The user provided does not exist.".to_owned(),
+		},
+		"server" => match find_option(&subcommand.options, "server_id") {
+			Some(CommandDataOptionValue::String(id)) => {
+				match id.trim().parse::<u64>().ok().filter(|id| *id != 0) {
+					Some(id) => {
+						let muted = {
+							let mut mute_list = handler.mute_list.lock().await;
+							let guilds = &mut mute_list.guild(guild_id).guilds;
+
+							match guilds.entry(GuildId(id)) {
+								Entry::Occupied(_) => false,
+								Entry::Vacant(v) => {
+									v.insert(entry.to_owned());
+									true
+								}
+							}
+						};
+
+						if !muted {
+							"This server is already muted.".to_owned()
+						} else {
+							handler.save_mutes().await;
+							handler
+								.mod_log
+								.lock()
+								.await
+								.post(
+									context,
+									Some(guild_id),
+									ModLogEvent::Muted {
+										target: format!("the server `{id}`"),
+										entry: &entry,
+									},
+								)
+								.await;
+
+							format!("Muted the server `{id}`, messages sent there will no longer be shown here.")
+						}
+					}
+					None => format!("`{id}` is not a valid server ID."),
+				}
+			}
+			_ => "Expected a server ID.".to_owned(),
+		},
+		"list" => {
+			// Looked up without `guild`, which would add an entry for the server.
+			let mutes = handler
+				.mute_list
+				.lock()
+				.await
+				.list
+				.get(&guild_id)
+				.cloned()
+				.unwrap_or_default();
+
+			return command
+				.create_interaction_response(&context.http, |r| {
+					r.kind(InteractionResponseType::ChannelMessageWithSource);
+					r.interaction_response_data(|rd| {
+						rd.embed(|e| {
+							e.title("Muted on this server").colour(Colour::ORANGE);
+
+							if mutes.users.is_empty() && mutes.guilds.is_empty() {
+								e.description("Nobody is muted on this server.");
+							}
+
+							for (user, entry) in mutes.users.iter().take(12) {
+								e.field(
+									user.to_string(),
+									format!("<@{user}> by <@{}>: {}", entry.executor, entry.reason),
+									false,
+								);
+							}
+
+							for (guild, entry) in mutes.guilds.iter().take(12) {
+								e.field(
+									format!("Server {guild}"),
+									format!("By <@{}>: {}", entry.executor, entry.reason),
+									false,
+								);
+							}
+
+							e
+						})
+						.ephemeral(true)
+					})
+				})
+				.await;
+		}
+		_ => "Unknown subcommand.".to_owned(),
+	};
+
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.content(content);
+				rd.ephemeral(true)
+			})
+		})
+		.await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+	command
+		.name("link-mute")
+		.description("Stops messages from other servers from being shown on this server.")
+		.default_member_permissions(Permissions::MANAGE_MESSAGES)
+		.dm_permission(false)
+		.create_option(|option| {
+			option
+				.name("user")
+				.description("Hides a user's messages on this server.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("user")
+						.description("The user to mute.")
+						.kind(CommandOptionType::User)
+						.required(true)
+				})
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("reason")
+						.description("Why was this user muted?")
+						.kind(CommandOptionType::String)
+						.required(false)
+				})
+		})
+		.create_option(|option| {
+			option
+				.name("server")
+				.description("Hides every message sent on another server.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("server_id")
+						.description("The ID of the server to mute.")
+						.kind(CommandOptionType::String)
+						.required(true)
+				})
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("reason")
+						.description("Why was this server muted?")
+						.kind(CommandOptionType::String)
+						.required(false)
+				})
+		})
+		.create_option(|option| {
+			option
+				.name("list")
+				.description("Lists the users and servers muted on this server.")
+				.kind(CommandOptionType::SubCommand)
+		})
+}
@@ -0,0 +1,80 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::prelude::Context;
+use serenity::utils::Colour;
+
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+
+pub async fn run(
+	_options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let client_id = handler.config.read().await.mqtt.client_id.to_owned();
+	let directory = handler.peer_directory.lock().await.to_owned();
+	let peers = directory.peers(&client_id);
+
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.embed(|e| {
+					e.title("Bots on the network").colour(Colour::BLURPLE);
+
+					if peers.is_empty() {
+						e.description("No other bots have been seen on the network.");
+					}
+
+					for (client_id, peer) in peers.iter().take(25) {
+						let state = if directory.is_online(peer) {
+							"🟢 Online"
+						} else {
+							"🔴 Offline"
+						};
+						let last_seen = format!(
+							"Last seen {} minutes ago",
+							peer.last_seen.elapsed().as_secs() / 60
+						);
+
+						let (name, details) = match &peer.heartbeat {
+							Some(h) => {
+								let channels = if h.channels.is_empty() {
+									"No linked channels".to_owned()
+								} else {
+									h.channels
+										.iter()
+										.map(|c| format!("#{} in {}", c.channel, c.guild))
+										.collect::<Vec<_>>()
+										.join("\n")
+								};
+
+								(
+									format!("{} (`{client_id}`)", h.name),
+									format!(
+										"{state}, version {}, up for {} minutes\n{last_seen}\n{channels}",
+										h.version,
+										h.uptime / 60
+									),
+								)
+							}
+							None => (format!("`{client_id}`"), format!("{state}\n{last_seen}")),
+						};
+
+						e.field(name, details, false);
+					}
+
+					e
+				})
+				.ephemeral(true)
+			})
+		})
+		.await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+	command
+		.name("link-peers")
+		.description("Lists the other bots on the network and whether they are online.")
+}
@@ -0,0 +1,71 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::prelude::Context;
+use serenity::utils::Colour;
+
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::permissions::tier_of;
+
+pub async fn run(
+	_options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let permissions = &handler.config.read().await.permissions.to_owned();
+	let tier = tier_of(permissions, command.user.id, command.member.as_ref());
+
+	let mut commands: Vec<_> = permissions.commands.iter().collect();
+	commands.sort();
+
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.embed(|e| {
+					e.title("Chat link permissions")
+						.colour(Colour::BLURPLE)
+						.description(format!("Your tier is **{tier:?}**. Each tier can use the commands of the tiers below it, commands not listed here can be used by everyone as long as this server's permissions allow it."))
+						.field("Owners", mention_list(&permissions.owners, "<@", ">"), false)
+						.field(
+							"Moderator roles",
+							mention_list(&permissions.moderator_roles, "<@&", ">"),
+							false,
+						)
+						.field(
+							"Commands",
+							if commands.is_empty() {
+								"None".to_owned()
+							} else {
+								commands
+									.iter()
+									.map(|(c, t)| format!("`/{c}`: {t:?}"))
+									.collect::<Vec<_>>()
+									.join("\n")
+							},
+							false,
+						)
+				})
+				.ephemeral(true)
+			})
+		})
+		.await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+	command
+		.name("link-permissions")
+		.description("Shows who can use each of the bot's commands.")
+}
+
+fn mention_list(ids: &[u64], prefix: &str, suffix: &str) -> String {
+	if ids.is_empty() {
+		return "None".to_owned();
+	}
+
+	ids.iter()
+		.map(|id| format!("{prefix}{id}{suffix}"))
+		.collect::<Vec<_>>()
+		.join(", ")
+}
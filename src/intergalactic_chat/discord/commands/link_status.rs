@@ -0,0 +1,128 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption,
+};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::prelude::Context;
+use serenity::utils::Colour;
+
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::mqtt::subscriptions;
+
+pub async fn run(
+	_options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let config = handler.config.read().await.to_owned();
+	let mqtt_status = handler.mqtt_status.lock().await.to_owned();
+	let link_status = handler.link_status.lock().await.to_owned();
+	let webhooks = handler.webhooks.lock().await.len();
+	let degraded = handler.webhook_health.degraded().await;
+	let (peers_online, peers_known) = {
+		let directory = handler.peer_directory.lock().await;
+		let peers = directory.peers(&config.mqtt.client_id);
+
+		(
+			peers.iter().filter(|(_, p)| directory.is_online(p)).count(),
+			peers.len(),
+		)
+	};
+	let (cache_len, cache_size) = {
+		let message_cache = handler.message_cache.lock().await;
+
+		(message_cache.len(), message_cache.size())
+	};
+
+	let broker = match mqtt_status.connected_since {
+		Some(since) => format!(
+			"Connected to `{}:{}` for {} minutes",
+			config.mqtt.broker_ip,
+			config.mqtt.broker_port,
+			since.elapsed().as_secs() / 60
+		),
+		None => format!(
+			"Disconnected from `{}:{}`: {}",
+			config.mqtt.broker_ip,
+			config.mqtt.broker_port,
+			mqtt_status
+				.last_error
+				.as_deref()
+				.unwrap_or("Not connected yet")
+		),
+	};
+
+	let peers = link_status.recent_peers();
+	let peers = if peers.is_empty() {
+		"None".to_owned()
+	} else {
+		peers
+			.iter()
+			.take(15)
+			.map(|(guild, elapsed)| format!("`{guild}`, {} minutes ago", elapsed.as_secs() / 60))
+			.collect::<Vec<_>>()
+			.join("\n")
+	};
+
+	let degraded = if degraded.is_empty() {
+		"None".to_owned()
+	} else {
+		degraded
+			.iter()
+			.map(|(channel, failure)| format!("<#{channel}>: {failure}"))
+			.collect::<Vec<_>>()
+			.join("\n")
+	};
+
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.embed(|e| {
+					e.title("Chat link status")
+						.colour(if mqtt_status.connected_since.is_some() {
+							Colour::DARK_GREEN
+						} else {
+							Colour::RED
+						})
+						.field("Broker", broker, false)
+						.field("Reconnects", mqtt_status.reconnects, true)
+						.field(
+							"Uptime",
+							format!("{} minutes", link_status.started.elapsed().as_secs() / 60),
+							true,
+						)
+						.field(
+							"Subscribed topics",
+							subscriptions(&config.mqtt)
+								.iter()
+								.map(|(topic, _)| format!("`{topic}`"))
+								.collect::<Vec<_>>()
+								.join("\n"),
+							false,
+						)
+						.field("Linked channels", config.discord.channels.len(), true)
+						.field("Webhooks", webhooks, true)
+						.field("Degraded channels", degraded, false)
+						.field(
+							"Message cache",
+							format!("{cache_len} of {cache_size} entries"),
+							true,
+						)
+						.field(
+							"Bots online",
+							format!("{peers_online} of {peers_known}, see `/link-peers`"),
+							false,
+						)
+						.field("Peers seen recently", peers, false)
+				})
+				.ephemeral(true)
+			})
+		})
+		.await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+	command
+		.name("link-status")
+		.description("Shows the health of the chat link.")
+}
@@ -0,0 +1,125 @@
+use serenity::builder::CreateApplicationCommand;
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::GuildId;
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+
+use super::{find_option, guild_and_executor, respond_guild_only};
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::mod_log::ModLogEvent;
+
+pub async fn run(
+	options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let subcommand = options.first().expect("Expected subcommand");
+	let (guild_id, executor) = match guild_and_executor(command) {
+		Some(v) => v,
+		None => return respond_guild_only(command, context).await,
+	};
+
+	let (target, removed) = match subcommand.name.as_str() {
+		"user" => match find_option(&subcommand.options, "user") {
+			Some(CommandDataOptionValue::User(user, _)) => (
+				format!("<@{}>", user.id),
+				handler
+					.mute_list
+					.lock()
+					.await
+					.guild(guild_id)
+					.users
+					.remove(&user.id),
+			),
+			_ => ("The user provided".to_owned(), None),
+		},
+		"server" => match find_option(&subcommand.options, "server_id") {
+			Some(CommandDataOptionValue::String(id)) => {
+				match id.trim().parse::<u64>().ok().filter(|id| *id != 0) {
+					Some(id) => (
+						format!("the server `{id}`"),
+						handler
+							.mute_list
+							.lock()
+							.await
+							.guild(guild_id)
+							.guilds
+							.remove(&GuildId(id)),
+					),
+					None => (format!("`{id}`"), None),
+				}
+			}
+			_ => ("The server provided".to_owned(), None),
+		},
+		_ => ("That".to_owned(), None),
+	};
+
+	let content = match removed {
+		Some(_) => {
+			handler.save_mutes().await;
+			handler
+				.mod_log
+				.lock()
+				.await
+				.post(
+					context,
+					Some(guild_id),
+					ModLogEvent::Unmuted {
+						target: target.to_owned(),
+						executor,
+					},
+				)
+				.await;
+
+			format!("Unmuted {target}, their messages will be shown here again.")
+		}
+		None => format!("{target} is not muted on this server."),
+	};
+
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.content(content);
+				rd.ephemeral(true)
+			})
+		})
+		.await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+	command
+		.name("link-unmute")
+		.description("Shows messages from a muted user or server on this server again.")
+		.default_member_permissions(Permissions::MANAGE_MESSAGES)
+		.dm_permission(false)
+		.create_option(|option| {
+			option
+				.name("user")
+				.description("Unmutes a user.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("user")
+						.description("The user to unmute.")
+						.kind(CommandOptionType::User)
+						.required(true)
+				})
+		})
+		.create_option(|option| {
+			option
+				.name("server")
+				.description("Unmutes a server.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("server_id")
+						.description("The ID of the server to unmute.")
+						.kind(CommandOptionType::String)
+						.required(true)
+				})
+		})
+}
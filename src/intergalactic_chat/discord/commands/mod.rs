@@ -1,4 +1,52 @@
-pub mod ping;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+};
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::prelude::Context;
+
 pub mod about;
+pub mod link_mute;
+pub mod link_peers;
+pub mod link_permissions;
+pub mod link_status;
+pub mod link_unmute;
 pub mod network_ban;
+pub mod network_bans;
 pub mod network_unban;
+pub mod ping;
+
+/// Sent when a command that needs a server is used in a direct message.
+const GUILD_ONLY_MESSAGE: &str = "This command can only be used in a server.";
+
+/// Finds the resolved value of the option called `name`.
+pub fn find_option<'a>(
+	options: &'a [CommandDataOption], name: &str,
+) -> Option<&'a CommandDataOptionValue> {
+	options
+		.iter()
+		.find(|o| o.name == name)
+		.and_then(|o| o.resolved.as_ref())
+}
+
+/// The server a command was used in and the user who used it, `None` in a
+/// direct message. Commands that need a server are registered with
+/// `dm_permission` off, but are checked anyway.
+pub fn guild_and_executor(command: &ApplicationCommandInteraction) -> Option<(GuildId, UserId)> {
+	Some((command.guild_id?, command.member.as_ref()?.user.id))
+}
+
+/// Tells the user that `command` can only be used in a server.
+pub async fn respond_guild_only(
+	command: &ApplicationCommandInteraction, context: &Context,
+) -> Result<(), serenity::Error> {
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.content(GUILD_ONLY_MESSAGE);
+				rd.ephemeral(true)
+			})
+		})
+		.await
+}
@@ -1,77 +1,160 @@
 use serenity::builder::CreateApplicationCommand;
+use serenity::http::CacheHttp;
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::prelude::interaction::application_command::{
 	ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
 };
 use serenity::model::prelude::interaction::InteractionResponseType;
-use serenity::model::Permissions;
+use serenity::model::prelude::{User, UserId};
+use serenity::model::{Permissions, Timestamp};
 use serenity::prelude::Context;
 
-use crate::intergalactic_chat::discord::bans::BanEntry;
+use super::{find_option, guild_and_executor, respond_guild_only};
+use crate::intergalactic_chat::discord::bans::{parse_duration, BanEntry};
 use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::mod_log::ModLogEvent;
 
 pub async fn run(
 	options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
 	handler: &DiscordHandler,
 ) -> Result<(), serenity::Error> {
-	let user_option = options
-		.get(0)
-		.expect("Expected user option")
-		.resolved
-		.as_ref()
-		.expect("Expected user object");
-	let reason_option = options
-		.get(1)
-		.expect("Expected `reason` option")
-		.resolved
-		.as_ref()
-		.expect("Expected string");
-
-	let content = if let CommandDataOptionValue::User(user, _) = user_option {
-		// If the user ID is already in the ban list:
-		if handler.ban_list.lock().await.list.get(&user.id).is_some() {
-			"This user has already been banned. Are you looking for the `/network-unban` command?"
-				.to_owned()
-		} else {
-			user.bot.then(|| "You cannot network ban bot users, if you wish to achieve the same result, try updating their permissions"
-			.to_owned());
-
-			let reason = if let CommandDataOptionValue::String(reason) = reason_option {
-				reason.to_owned()
-			} else {
-				"Invalid reason".to_owned()
-			};
-
-			handler.ban_list.lock().await.list.insert(
-				user.id,
-				// These unwraps are safe because the command is only registered in guilds
-				// and will never be `None`.
-				BanEntry {
-					reason: reason.to_owned(),
-					executor: command.member.to_owned().unwrap().user.id,
-					ban_origin: command.guild_id.unwrap(),
-					timestamp: command.id.created_at(),
+	let (origin, executor) = match guild_and_executor(command) {
+		Some(v) => v,
+		None => return respond_guild_only(command, context).await,
+	};
+	let reason = match find_option(options, "reason") {
+		Some(CommandDataOptionValue::String(reason)) => reason.to_owned(),
+		_ => "Invalid reason".to_owned(),
+	};
+	let duration_option = find_option(options, "duration");
+
+	// Bans without a duration are permanent.
+	let expires_at = match duration_option {
+		Some(CommandDataOptionValue::String(d)) => {
+			let expires_at = parse_duration(d).and_then(|d| {
+				let now = command.id.created_at().unix_timestamp();
+
+				Timestamp::from_unix_timestamp(now.checked_add(d.as_secs().try_into().ok()?)?).ok()
+			});
+
+			if expires_at.is_none() {
+				let content = format!(
+					"\"{d}\" is not a valid duration, try something like `30m`, `12h` or `7d`."
+				);
+
+				return respond(command, context, content).await;
+			}
+
+			expires_at
+		}
+		_ => None,
+	};
+
+	// Users can be banned by ID so that users who are only on another linked
+	// server can be banned too.
+	let user = match (
+		find_option(options, "user"),
+		find_option(options, "user_id"),
+	) {
+		(Some(CommandDataOptionValue::User(user, _)), _) => user.to_owned(),
+		(_, Some(CommandDataOptionValue::String(id))) => {
+			match id.trim().parse::<u64>().ok().filter(|id| *id != 0) {
+				Some(id) => match UserId(id).to_user(&context).await {
+					Ok(user) => user,
+					Err(_) => {
+						return respond(
+							command,
+							context,
+							format!("No user with the ID `{id}` exists."),
+						)
+						.await
+					}
 				},
-			);
-
-			let was_notified_message = match user.dm(&context, |dm| {
-				dm.content(format!("You have been network banned by a moderator. This prevents your messages from being sent to other servers, but you can still read and sent messages in linked channels.\n\nThe moderators have provided a reason for your ban:\n\"{}\"", reason))
-			}).await {
-				Ok(_) => "successfully notified",
-				Err(_) => "unable to be notified"
-			};
-
-			format!(
-				"Banned <@{}> from the chat link with the reason: \"{}\"\n\nThe user was {} by direct message.",
-				user.id,
-				reason.to_owned(),
-				was_notified_message
+				None => {
+					return respond(command, context, format!("`{id}` is not a valid user ID."))
+						.await
+				}
+			}
+		}
+		_ => {
+			return respond(
+				command,
+				context,
+				"Provide either a `user` or a `user_id` to ban.".to_owned(),
 			)
+			.await
 		}
+	};
+
+	let already_banned = handler.ban_list.lock().await.list.contains_key(&user.id);
+
+	let content = if already_banned {
+		"This user has already been banned. Are you looking for the `/network-unban` command?"
+			.to_owned()
+	} else if user.bot {
+		"You cannot network ban bot users, if you wish to achieve the same result, try updating their permissions"
+			.to_owned()
 	} else {
-		"The user provided does not exist.".to_owned()
+		let entry = BanEntry {
+			reason: reason.to_owned(),
+			executor,
+			ban_origin: origin,
+			timestamp: command.id.created_at(),
+			expires_at,
+		};
+
+		handler
+			.ban_list
+			.lock()
+			.await
+			.list
+			.insert(user.id, entry.to_owned());
+		handler.save_bans().await;
+
+		handler
+			.mod_log
+			.lock()
+			.await
+			.post_all(
+				context,
+				ModLogEvent::Ban {
+					user: &user,
+					entry: &entry,
+				},
+			)
+			.await;
+
+		let was_notified_message = if notify_banned(context, &user, &entry).await {
+			"successfully notified"
+		} else {
+			"unable to be notified"
+		};
+
+		format!(
+			"Banned <@{}> from the chat link with the reason: \"{}\"\n\nThe ban expires: {}\n\nThe user was {} by direct message.",
+			user.id,
+			reason.to_owned(),
+			entry.display_expiry(),
+			was_notified_message
+		)
 	};
 
+	respond(command, context, content).await
+}
+
+/// Tells `user` they were banned by direct message, returning whether the
+/// message was sent.
+pub async fn notify_banned(cache_http: impl CacheHttp, user: &User, entry: &BanEntry) -> bool {
+	user.dm(cache_http, |dm| {
+		dm.content(format!("You have been network banned by a moderator. This prevents your messages from being sent to other servers, but you can still read and sent messages in linked channels.\n\nThe moderators have provided a reason for your ban:\n\"{}\"\n\nYour ban expires: {}", entry.reason, entry.display_expiry()))
+	})
+	.await
+	.is_ok()
+}
+
+async fn respond(
+	command: &ApplicationCommandInteraction, context: &Context, content: String,
+) -> Result<(), serenity::Error> {
 	command
 		.create_interaction_response(&context.http, |r| {
 			r.kind(InteractionResponseType::ChannelMessageWithSource);
@@ -83,23 +166,43 @@ pub async fn run(
 		.await
 }
 
+/// Discord requires required options to come first, so `reason` is before
+/// `user`, which became optional when banning by `user_id` was added. Options
+/// are read by name, so the order only changes how the command is shown.
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
 	command
 		.name("network-ban")
 		.description("Prevents a users messages from being propagated.")
 		.default_member_permissions(Permissions::BAN_MEMBERS)
+		.dm_permission(false)
+		.create_option(|option| {
+			option
+				.name("reason")
+				.description("Why was this user banned?")
+				.kind(CommandOptionType::String)
+				.required(true)
+		})
 		.create_option(|option| {
 			option
 				.name("user")
 				.description("The user to ban.")
 				.kind(CommandOptionType::User)
-				.required(true)
+				.required(false)
 		})
 		.create_option(|option| {
 			option
-				.name("reason")
-				.description("Why was this user banned?")
+				.name("user_id")
+				.description("The ID of the user to ban, for users that aren't on this server.")
 				.kind(CommandOptionType::String)
-				.required(true)
+				.required(false)
+		})
+		.create_option(|option| {
+			option
+				.name("duration")
+				.description(
+					"How long the ban lasts, such as 30m, 12h or 7d. Permanent if not set.",
+				)
+				.kind(CommandOptionType::String)
+				.required(false)
 		})
 }
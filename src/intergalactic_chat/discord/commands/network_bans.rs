@@ -0,0 +1,348 @@
+use std::borrow::Cow;
+
+use serenity::builder::{CreateApplicationCommand, CreateComponents, CreateEmbed};
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::prelude::component::ButtonStyle;
+use serenity::model::prelude::interaction::application_command::{
+	ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+};
+use serenity::model::prelude::interaction::message_component::MessageComponentInteraction;
+use serenity::model::prelude::interaction::InteractionResponseType;
+use serenity::model::prelude::{AttachmentType, UserId};
+use serenity::model::Permissions;
+use serenity::prelude::Context;
+use serenity::utils::Colour;
+
+use super::{guild_and_executor, respond_guild_only};
+use crate::intergalactic_chat::discord::bans::{parse_ban_import, BanEntry, BanList};
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::mod_log::ModLogEvent;
+
+/// The number of bans shown on each page of `/network-bans list`.
+const PAGE_SIZE: usize = 5;
+
+/// Prefix of the custom ID of the pagination buttons, followed by the page
+/// number.
+pub const PAGE_BUTTON_PREFIX: &str = "network-bans:page:";
+
+pub async fn run(
+	options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let subcommand = options.first().expect("Expected subcommand");
+	let ban_list = handler.ban_list.lock().await.to_owned();
+
+	match subcommand.name.as_str() {
+		"list" => {
+			command
+				.create_interaction_response(&context.http, |r| {
+					r.kind(InteractionResponseType::ChannelMessageWithSource);
+					r.interaction_response_data(|rd| {
+						let (embed, components) = build_page(&ban_list, 0);
+
+						rd.set_embed(embed)
+							.set_components(components)
+							.ephemeral(true)
+					})
+				})
+				.await
+		}
+		"info" => {
+			let user_option = subcommand
+				.options
+				.first()
+				.expect("Expected user option")
+				.resolved
+				.as_ref()
+				.expect("Expected user object");
+
+			let user = match user_option {
+				CommandDataOptionValue::User(user, _) => user,
+				_ => return respond(command, context, "The user provided does not exist.").await,
+			};
+
+			let entry = match ban_list.list.get(&user.id) {
+				Some(e) => e,
+				None => return respond(command, context, "This user is not banned.").await,
+			};
+
+			command
+				.create_interaction_response(&context.http, |r| {
+					r.kind(InteractionResponseType::ChannelMessageWithSource);
+					r.interaction_response_data(|rd| {
+						rd.embed(|e| {
+							e.title(format!("Network ban for {}", user.tag()))
+								.colour(Colour::RED)
+								.description(describe_ban(&user.id, entry))
+						})
+						.ephemeral(true)
+					})
+				})
+				.await
+		}
+		"export" => {
+			let format = subcommand.options.first().and_then(|o| o.resolved.as_ref());
+
+			let (data, filename) = match format {
+				Some(CommandDataOptionValue::String(f)) if f == "csv" => {
+					(ban_list.to_csv(), "bans.csv")
+				}
+				_ => (
+					serde_json::to_string_pretty(&ban_list).unwrap(),
+					"bans.json",
+				),
+			};
+
+			command
+				.create_interaction_response(&context.http, |r| {
+					r.kind(InteractionResponseType::ChannelMessageWithSource);
+					r.interaction_response_data(|rd| {
+						rd.content(format!("Exported {} bans.", ban_list.list.len()))
+							.add_file(AttachmentType::Bytes {
+								data: Cow::from(data.into_bytes()),
+								filename: filename.to_owned(),
+							})
+							.ephemeral(true)
+					})
+				})
+				.await
+		}
+		"import" => {
+			let attachment = match subcommand.options.first().and_then(|o| o.resolved.as_ref()) {
+				Some(CommandDataOptionValue::Attachment(a)) => a,
+				_ => return respond(command, context, "Expected a file to import.").await,
+			};
+			let data = match attachment.download().await {
+				Ok(d) => String::from_utf8_lossy(&d).into_owned(),
+				Err(e) => {
+					let content = format!("Unable to download {}: {e}", attachment.filename);
+
+					return respond(command, context, &content).await;
+				}
+			};
+
+			let (bans, errors) =
+				parse_ban_import(&data, attachment.filename.to_lowercase().ends_with(".csv"));
+			let (origin, executor) = match guild_and_executor(command) {
+				Some(v) => v,
+				None => return respond_guild_only(command, context).await,
+			};
+			let now = command.id.created_at();
+			let (mut added, mut skipped) = (0, 0);
+
+			{
+				let mut ban_list = handler.ban_list.lock().await;
+
+				for ban in bans {
+					let expired = ban
+						.expires_at
+						.is_some_and(|e| e.unix_timestamp() <= now.unix_timestamp());
+
+					if expired || ban_list.list.contains_key(&ban.user) {
+						skipped += 1;
+						continue;
+					}
+
+					ban_list.list.insert(
+						ban.user,
+						BanEntry {
+							reason: if ban.reason.is_empty() {
+								"Imported ban".to_owned()
+							} else {
+								ban.reason
+							},
+							executor,
+							ban_origin: origin,
+							timestamp: now,
+							expires_at: ban.expires_at,
+						},
+					);
+					added += 1;
+				}
+			}
+
+			if added > 0 {
+				handler.save_bans().await;
+			}
+
+			handler
+				.mod_log
+				.lock()
+				.await
+				.post_all(
+					context,
+					ModLogEvent::BansImported {
+						executor,
+						origin,
+						added,
+						skipped,
+					},
+				)
+				.await;
+
+			let mut content = format!(
+				"Imported {added} bans from {}, skipping {skipped} that were already banned or expired.",
+				attachment.filename
+			);
+
+			if !errors.is_empty() {
+				content.push_str(&format!("\n\n{} lines could not be read:", errors.len()));
+
+				for error in errors.iter().take(10) {
+					content.push_str(&format!("\n- {error}"));
+				}
+
+				if errors.len() > 10 {
+					content.push_str(&format!("\n- ...and {} more", errors.len() - 10));
+				}
+			}
+
+			respond(command, context, &content).await
+		}
+		_ => respond(command, context, "Unknown subcommand.").await,
+	}
+}
+
+/// Handles the pagination buttons of `/network-bans list`.
+pub async fn run_page(
+	component: &MessageComponentInteraction, context: &Context, handler: &DiscordHandler,
+) -> Result<(), serenity::Error> {
+	let page = component
+		.data
+		.custom_id
+		.trim_start_matches(PAGE_BUTTON_PREFIX)
+		.parse::<usize>()
+		.unwrap_or(0);
+	let ban_list = handler.ban_list.lock().await.to_owned();
+
+	component
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::UpdateMessage);
+			r.interaction_response_data(|rd| {
+				let (embed, components) = build_page(&ban_list, page);
+
+				rd.set_embed(embed).set_components(components)
+			})
+		})
+		.await
+}
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+	command
+		.name("network-bans")
+		.description("View and manage the users banned from the network.")
+		.default_member_permissions(Permissions::BAN_MEMBERS)
+		.dm_permission(false)
+		.create_option(|option| {
+			option
+				.name("list")
+				.description("Lists every network ban.")
+				.kind(CommandOptionType::SubCommand)
+		})
+		.create_option(|option| {
+			option
+				.name("info")
+				.description("Shows the network ban of a user.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("user")
+						.description("The user to look up.")
+						.kind(CommandOptionType::User)
+						.required(true)
+				})
+		})
+		.create_option(|option| {
+			option
+				.name("export")
+				.description("Exports every network ban as a file.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("format")
+						.description("The format of the file, JSON if not set.")
+						.kind(CommandOptionType::String)
+						.add_string_choice("JSON", "json")
+						.add_string_choice("CSV", "csv")
+						.required(false)
+				})
+		})
+		.create_option(|option| {
+			option
+				.name("import")
+				.description("Bans every user in a JSON or CSV file of user IDs and reasons.")
+				.kind(CommandOptionType::SubCommand)
+				.create_sub_option(|sub_option| {
+					sub_option
+						.name("file")
+						.description("The file to import.")
+						.kind(CommandOptionType::Attachment)
+						.required(true)
+				})
+		})
+}
+
+/// Builds the embed and pagination buttons for `page` of the ban list.
+fn build_page(ban_list: &BanList, page: usize) -> (CreateEmbed, CreateComponents) {
+	let mut bans: Vec<(&UserId, &BanEntry)> = ban_list.list.iter().collect();
+	bans.sort_by_key(|(_, e)| std::cmp::Reverse(e.timestamp.unix_timestamp()));
+
+	let pages = bans.len().div_ceil(PAGE_SIZE).max(1);
+	let page = page.min(pages - 1);
+
+	let mut embed = CreateEmbed::default();
+	embed
+		.title(format!("Network bans (page {} of {})", page + 1, pages))
+		.colour(Colour::RED);
+
+	if bans.is_empty() {
+		embed.description("Nobody is banned from the network.");
+	}
+
+	for (user, entry) in bans.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+		embed.field(user.to_string(), describe_ban(user, entry), false);
+	}
+
+	let mut components = CreateComponents::default();
+	components.create_action_row(|row| {
+		row.create_button(|b| {
+			b.custom_id(format!("{PAGE_BUTTON_PREFIX}{}", page.saturating_sub(1)))
+				.label("Previous")
+				.style(ButtonStyle::Secondary)
+				.disabled(page == 0)
+		})
+		.create_button(|b| {
+			b.custom_id(format!("{PAGE_BUTTON_PREFIX}{}", page + 1))
+				.label("Next")
+				.style(ButtonStyle::Secondary)
+				.disabled(page + 1 >= pages)
+		})
+	});
+
+	(embed, components)
+}
+
+fn describe_ban(user: &UserId, entry: &BanEntry) -> String {
+	format!(
+		"**User:** <@{user}>\n**Reason:** {}\n**Executor:** <@{}>\n**Origin server:** {}\n**Banned:** <t:{}:f>\n**Expires:** {}",
+		entry.reason,
+		entry.executor,
+		entry.display_origin(),
+		entry.timestamp.unix_timestamp(),
+		entry.display_expiry()
+	)
+}
+
+async fn respond(
+	command: &ApplicationCommandInteraction, context: &Context, content: &str,
+) -> Result<(), serenity::Error> {
+	command
+		.create_interaction_response(&context.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource);
+			r.interaction_response_data(|rd| {
+				rd.content(content);
+				rd.ephemeral(true)
+			})
+		})
+		.await
+}
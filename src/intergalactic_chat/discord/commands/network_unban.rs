@@ -7,25 +7,46 @@ use serenity::model::prelude::interaction::InteractionResponseType;
 use serenity::model::Permissions;
 use serenity::prelude::Context;
 
+use super::{guild_and_executor, respond_guild_only};
 use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::mod_log::ModLogEvent;
 
 pub async fn run(
 	options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
 	handler: &DiscordHandler,
 ) -> Result<(), serenity::Error> {
+	let (origin, executor) = match guild_and_executor(command) {
+		Some(v) => v,
+		None => return respond_guild_only(command, context).await,
+	};
 	let user_option = options
-		.get(0)
+		.first()
 		.expect("Expected user option")
 		.resolved
 		.as_ref()
 		.expect("Expected user object");
 
 	let content = if let CommandDataOptionValue::User(user, _) = user_option {
-		// If the user ID is not in the ban list:
-		if handler.ban_list.lock().await.list.get(&user.id).is_none() {
-			"This user is not banned. Are you looking for the `/network-ban` command?".to_owned()
-		} else {
-			handler.ban_list.lock().await.list.remove(&user.id);
+		let removed = handler.ban_list.lock().await.list.remove(&user.id);
+
+		// If the user ID was in the ban list:
+		if let Some(entry) = removed {
+			handler.save_bans().await;
+
+			handler
+				.mod_log
+				.lock()
+				.await
+				.post_all(
+					context,
+					ModLogEvent::Unban {
+						user,
+						executor,
+						origin,
+						entry: &entry,
+					},
+				)
+				.await;
 
 			let was_notified_message = match user.dm(&context, |dm| {
 				dm.content("You have been unbanned from the network by a moderator. Your messages can now be sent to other servers.".to_owned())
@@ -38,6 +59,8 @@ pub async fn run(
 				"Unbanned <@{}> from the chat link.\n\nThe user was {} by direct message.",
 				user.id, was_notified_message
 			)
+		} else {
+			"This user is not banned. Are you looking for the `/network-ban` command?".to_owned()
 		}
 	} else {
 		"The user provided does not exist.".to_owned()
@@ -59,6 +82,7 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
 		.name("network-unban")
 		.description("Removes a ban from a user if they have one.")
 		.default_member_permissions(Permissions::BAN_MEMBERS)
+		.dm_permission(false)
 		.create_option(|option| {
 			option
 				.name("user")
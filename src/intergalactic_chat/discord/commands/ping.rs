@@ -1,22 +1,69 @@
+use std::time::{Duration, Instant};
+
 use serenity::builder::CreateApplicationCommand;
+use serenity::client::bridge::gateway::ShardId;
 use serenity::model::prelude::interaction::application_command::{
 	ApplicationCommandInteraction, CommandDataOption,
 };
 use serenity::model::prelude::interaction::InteractionResponseType;
 use serenity::prelude::Context;
 
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::status::ShardManagerContainer;
+use crate::intergalactic_chat::mqtt::{probe_topic, round_trip};
+
 pub async fn run(
 	_options: &[CommandDataOption], command: &ApplicationCommandInteraction, context: &Context,
+	handler: &DiscordHandler,
 ) -> Result<(), serenity::Error> {
+	// Measuring the MQTT round-trip can take a few seconds.
 	command
 		.create_interaction_response(&context.http, |r| {
-			r.kind(InteractionResponseType::ChannelMessageWithSource);
-			r.interaction_response_data(|rd| {
-				rd.content("I'm alive!");
-				rd.ephemeral(true)
-			})
+			r.kind(InteractionResponseType::DeferredChannelMessageWithSource);
+			r.interaction_response_data(|rd| rd.ephemeral(true))
 		})
+		.await?;
+
+	let gateway = match context.data.read().await.get::<ShardManagerContainer>() {
+		Some(manager) => manager
+			.lock()
+			.await
+			.runners
+			.lock()
+			.await
+			.get(&ShardId(context.shard_id))
+			.and_then(|r| r.latency),
+		None => None,
+	};
+
+	let rest_start = Instant::now();
+	let rest = context
+		.http
+		.get_current_user()
 		.await
+		.ok()
+		.map(|_| rest_start.elapsed());
+
+	let probe_topic = probe_topic(&handler.config.read().await.mqtt);
+	let mqtt = round_trip(
+		&handler.mq_client,
+		&handler.mq_probes,
+		&probe_topic,
+		&command.id.to_string(),
+	)
+	.await;
+
+	command
+		.edit_original_interaction_response(&context.http, |r| {
+			r.content(format!(
+				"Pong!\n\nGateway heartbeat: {}\nREST round-trip: {}\nMQTT round-trip: {}",
+				display_latency(gateway),
+				display_latency(rest),
+				display_latency(mqtt)
+			))
+		})
+		.await
+		.map(|_| ())
 }
 
 pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
@@ -24,3 +71,10 @@ pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicatio
 		.name("ping")
 		.description("Returns the response time of the bot.")
 }
+
+fn display_latency(latency: Option<Duration>) -> String {
+	match latency {
+		Some(l) => format!("{}ms", l.as_millis()),
+		None => "Unavailable".to_owned(),
+	}
+}
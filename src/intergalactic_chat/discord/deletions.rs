@@ -0,0 +1,149 @@
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use serenity::model::prelude::{ChannelId, GuildId, MessageId};
+
+use super::bot::DiscordHandler;
+use super::mod_log::ModLogEvent;
+use crate::intergalactic_chat::config::MirrorDeletions;
+use crate::intergalactic_chat::mqtt::deletion_topic;
+
+/// Published when a moderator deletes a mirrored message and the bot's policy
+/// is [`MirrorDeletions::Propagate`], so the original message and every other
+/// copy are deleted too.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MirrorDeletion {
+	/// The message the deleted mirror was a copy of.
+	pub origin_message: MessageId,
+	/// The channel the original message was sent in, if known.
+	pub origin_channel: Option<ChannelId>,
+	/// The server the mirror was deleted in.
+	pub deleted_in: Option<GuildId>,
+}
+
+/// Handles the deletion of `mirror` in `guild`. Returns `false` if `mirror`
+/// isn't a copy of a cached message.
+pub async fn mirror_deleted(
+	handler: &DiscordHandler, mirror: MessageId, guild: Option<GuildId>,
+) -> bool {
+	let (origin, policy, topic) = {
+		let mut message_cache = handler.message_cache.lock().await;
+		let origin = match message_cache.origin_of(&mirror) {
+			Some(o) => o,
+			None => return false,
+		};
+
+		message_cache.remove_mirror(&mirror);
+
+		let config = handler.config.read().await;
+
+		(
+			origin,
+			config.discord.mirror_deletions,
+			deletion_topic(&config.mqtt),
+		)
+	};
+
+	if policy == MirrorDeletions::Local {
+		return true;
+	}
+
+	let (origin_message, origin_channel) = origin;
+	let deletion = MirrorDeletion {
+		origin_message,
+		origin_channel,
+		deleted_in: guild,
+	};
+
+	println!("A copy of {origin_message} was deleted, deleting the original and every copy");
+
+	// The bot receives its own deletion too, which deletes the copies here.
+	if let Err(e) = handler
+		.mq_client
+		.publish(
+			topic,
+			QoS::AtLeastOnce,
+			false,
+			serde_json::to_string(&deletion).unwrap(),
+		)
+		.await
+	{
+		println!("Error publishing the deletion of {origin_message}: {e}");
+	}
+
+	true
+}
+
+/// Deletes the original message and every cached copy of a [`MirrorDeletion`]
+/// received from the network, unless this bot only removes mirrors locally.
+pub async fn apply(handler: &DiscordHandler, http: &Http, deletion: MirrorDeletion) {
+	let (policy, linked) = {
+		let config = handler.config.read().await;
+
+		(
+			config.discord.mirror_deletions,
+			deletion
+				.origin_channel
+				.is_some_and(|c| config.discord.channels.contains(c.as_u64())),
+		)
+	};
+
+	if policy == MirrorDeletions::Local {
+		return;
+	}
+
+	// Removing the entry first means the deletions below aren't handled again
+	// when Discord reports them.
+	let copies = {
+		let mut message_cache = handler.message_cache.lock().await;
+		let copies = message_cache
+			.get_entry(&deletion.origin_message)
+			.map(|(_, v)| v.to_owned())
+			.unwrap_or_default();
+
+		message_cache.remove(&deletion.origin_message);
+
+		copies
+	};
+
+	let mut deleted: Vec<ChannelId> = Vec::new();
+
+	if let (true, Some(channel)) = (linked, deletion.origin_channel) {
+		match channel.delete_message(http, deletion.origin_message).await {
+			Ok(()) => deleted.push(channel),
+			Err(e) => println!("Error deleting {}: {e}", deletion.origin_message),
+		}
+	}
+
+	for copy in copies {
+		// The mirror the moderator deleted is already gone.
+		if copy
+			.related_channel_id
+			.delete_message(http, copy.related_message_id)
+			.await
+			.is_ok()
+		{
+			deleted.push(copy.related_channel_id);
+		}
+	}
+
+	let mod_log = handler.mod_log.lock().await;
+
+	for channel in deleted {
+		let guild = mod_log.guild_of(channel);
+
+		if guild != deletion.deleted_in {
+			mod_log
+				.post(
+					http,
+					guild,
+					ModLogEvent::DeletionPropagated {
+						deleted_in: deletion.deleted_in,
+						origin_message: deletion.origin_message,
+						channel,
+					},
+				)
+				.await;
+		}
+	}
+}
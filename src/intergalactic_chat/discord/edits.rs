@@ -0,0 +1,122 @@
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use serenity::http::Http;
+use serenity::model::prelude::{ChannelId, GuildId, MessageId};
+use serenity::model::webhook::Webhook;
+
+use super::bot::DiscordHandler;
+use super::filter::FilterVerdict;
+use super::mod_log::ModLogEvent;
+use crate::intergalactic_chat::metrics::DropReason;
+use crate::intergalactic_chat::mqtt::edit_topic;
+
+/// Published when a message sent in a linked channel is edited, so every bot
+/// edits its copies of it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageEdit {
+	/// The edited message.
+	pub message: MessageId,
+	/// The channel the message was sent in.
+	pub channel: ChannelId,
+	pub guild: Option<GuildId>,
+	/// The new content, after the filter of the bot that published the edit.
+	pub content: String,
+}
+
+/// Publishes `edit` to every bot on the network.
+pub async fn publish(handler: &DiscordHandler, edit: &MessageEdit) {
+	let topic = edit_topic(&handler.config.read().await.mqtt);
+
+	// The bot receives its own edit too, which edits the copies here.
+	if let Err(e) = handler
+		.mq_client
+		.publish(
+			topic,
+			QoS::AtLeastOnce,
+			false,
+			serde_json::to_string(edit).unwrap(),
+		)
+		.await
+	{
+		println!("Error publishing the edit of {}: {e}", edit.message);
+	}
+}
+
+/// Edits every cached copy of a [`MessageEdit`] received from the network.
+pub async fn apply(handler: &DiscordHandler, http: &Http, mut edit: MessageEdit) {
+	if handler.config.read().await.filter.filter_received {
+		let result = handler.content_filter.lock().await.check(&edit.content);
+
+		for hit in &result.hits {
+			handler
+				.mod_log
+				.lock()
+				.await
+				.post_all(
+					http,
+					ModLogEvent::FilterHit {
+						author: None,
+						channel: edit.channel,
+						guild: edit.guild,
+						message: edit.message,
+						hit,
+						received: true,
+						edited: true,
+					},
+				)
+				.await;
+		}
+
+		match result.verdict {
+			FilterVerdict::Drop => {
+				handler.metrics.dropped(DropReason::Filtered);
+				return;
+			}
+			FilterVerdict::Redact(content) => edit.content = content,
+			FilterVerdict::Pass => (),
+		}
+	}
+
+	let mirrors = handler
+		.message_cache
+		.lock()
+		.await
+		.get_entry(&edit.message)
+		.map(|(_, v)| v.to_owned());
+	handler.metrics.observe_cache(mirrors.is_some());
+
+	let mirrors = match mirrors {
+		Some(v) => v,
+		None => return,
+	};
+
+	// Mirrors posted before a webhook was recreated can no longer be edited,
+	// so failures are reported and skipped.
+	for i in mirrors {
+		let edited = match Webhook::from_id(http, i.related_webhook_id).await {
+			Ok(webhook) => {
+				webhook
+					.edit_message(http, i.related_message_id, |m| {
+						m.content(edit.content.to_owned())
+					})
+					.await
+			}
+			Err(e) => Err(e),
+		};
+
+		if let Err(e) = edited {
+			println!("Error editing the mirror {}: {e}", i.related_message_id);
+			handler
+				.webhook_health
+				.report(
+					http,
+					&handler.webhooks,
+					&handler.mod_log,
+					i.related_channel_id,
+					i.related_webhook_id,
+					&e,
+				)
+				.await;
+		}
+	}
+}
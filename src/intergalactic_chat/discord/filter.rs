@@ -0,0 +1,403 @@
+use std::{
+	fs::OpenOptions,
+	io::{Read, Write},
+	time::SystemTime,
+};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Matches invite links to other Discord servers.
+const INVITE_PATTERN: &str =
+	r"(?:https?://)?(?:www\.)?(?:discord\.gg|discord(?:app)?\.com/invite)/[\w-]+";
+
+/// The text matched content is replaced with when a rule redacts it.
+const REDACTED: &str = "[redacted]";
+
+/// The rules file as it is written on disk.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FilterRules {
+	/// If set, invite links to other Discord servers are handled with this
+	/// action.
+	#[serde(default)]
+	pub block_invites: Option<FilterAction>,
+	#[serde(default, rename = "rule")]
+	pub rules: Vec<FilterRule>,
+}
+
+/// A single rule, matching either a list of words or a regular expression.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterRule {
+	pub name: String,
+	pub action: FilterAction,
+	/// Words matched on word boundaries, ignoring case.
+	#[serde(default)]
+	pub words: Vec<String>,
+	pub regex: Option<String>,
+}
+
+/// What happens to a message once a rule has matched it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+	/// The message is not propagated at all.
+	Drop,
+	/// The matched text is replaced and the message is propagated.
+	Redact,
+	/// The message is propagated unchanged. Every hit is reported to the
+	/// mod-log, so this only reports the message.
+	Flag,
+}
+
+/// A rule that matched a message.
+#[derive(Debug, Clone)]
+pub struct FilterHit {
+	pub rule: String,
+	pub action: FilterAction,
+	pub matched: String,
+}
+
+/// What should be done with a message after every rule has been checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterVerdict {
+	/// The message can be propagated as is.
+	Pass,
+	/// The message can be propagated with the provided content instead.
+	Redact(String),
+	/// The message must not be propagated.
+	Drop,
+}
+
+#[derive(Debug, Clone)]
+pub struct FilterResult {
+	pub verdict: FilterVerdict,
+	pub hits: Vec<FilterHit>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+	name: String,
+	action: FilterAction,
+	pattern: Regex,
+}
+
+/// The compiled form of [`FilterRules`], used to check messages before they
+/// are propagated.
+#[derive(Debug, Clone, Default)]
+pub struct ContentFilter {
+	rules: Vec<CompiledRule>,
+	/// The modification time of the rules file when it was last read.
+	modified: Option<SystemTime>,
+}
+
+impl ContentFilter {
+	/// Compiles every rule in `rules`. Returns an error describing the first
+	/// rule that is invalid.
+	pub fn new(rules: &FilterRules) -> Result<Self, String> {
+		let mut compiled = Vec::new();
+
+		if let Some(action) = rules.block_invites {
+			compiled.push(CompiledRule {
+				name: "invite links".to_owned(),
+				action,
+				pattern: Regex::new(&format!("(?i){INVITE_PATTERN}")).unwrap(),
+			});
+		}
+
+		for rule in &rules.rules {
+			// An empty word would match at every word boundary, so everything.
+			if rule.words.iter().any(|w| w.trim().is_empty()) {
+				return Err(format!("Rule \"{}\" has an empty word", rule.name));
+			}
+
+			let pattern = match (&rule.regex, rule.words.is_empty()) {
+				(Some(regex), true) => regex.to_owned(),
+				(None, false) => format!(
+					r"\b(?:{})\b",
+					rule.words
+						.iter()
+						.map(|w| regex::escape(w))
+						.collect::<Vec<_>>()
+						.join("|")
+				),
+				_ => {
+					return Err(format!(
+						"Rule \"{}\" must have either `words` or `regex`, but not both",
+						rule.name
+					))
+				}
+			};
+
+			compiled.push(CompiledRule {
+				name: rule.name.to_owned(),
+				action: rule.action,
+				pattern: RegexBuilder::new(&pattern)
+					.case_insensitive(true)
+					.build()
+					.map_err(|e| format!("Rule \"{}\" has an invalid pattern: {e}", rule.name))?,
+			});
+		}
+
+		Ok(Self {
+			rules: compiled,
+			modified: None,
+		})
+	}
+
+	/// Attempts to read and compile the rules file (`path`). If the file does
+	/// not exist a new one is created containing an example rule set.
+	///
+	/// ## Panics
+	///
+	/// This function will panic if the file cannot be opened, read or written.
+	pub fn initialize(path: &str) -> Result<Self, String> {
+		let mut buf = String::new();
+		let default_rules = String::from(
+			r#"
+# These are the content filter rules for your bot. Changes to this file are
+# picked up while the bot is running.
+#
+# Each rule has an `action`, which is one of:
+#  - "drop":   the message is not sent to other servers.
+#  - "redact": the matched text is replaced with "[redacted]".
+#  - "flag":   the message is sent as is, and only reported.
+#
+# Every message matching a rule is reported to the mod-log channel, whatever
+# its action.
+
+# Uncomment to stop invite links to other servers from being propagated.
+# block_invites = "drop"
+
+# [[rule]]
+# name = "example words"
+# action = "redact"
+# words = ["example", "another example"]
+
+# [[rule]]
+# name = "example pattern"
+# action = "flag"
+# regex = "free\\s+nitro"
+"#,
+		);
+		let mut file = OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.read(true)
+			.write(true)
+			.open(path)
+			.map_err(|e| format!("Error trying to open {path}: {e}"))?;
+		file.read_to_string(&mut buf)
+			.map_err(|e| format!("Error trying to read {path}: {e}"))?;
+
+		if buf.is_empty() {
+			file.write_all(default_rules.as_bytes())
+				.map_err(|e| format!("Error trying to write to {path}: {e}"))?;
+			buf = default_rules;
+		}
+
+		let mut filter = Self::parse(&buf)
+			.map_err(|e| format!("The filter rules in {path} are invalid: {e}"))?;
+		filter.modified = modified_time(path);
+
+		Ok(filter)
+	}
+
+	/// Compiles the rules in `source`, the contents of a rules file.
+	pub fn parse(source: &str) -> Result<Self, String> {
+		let rules = toml::from_str::<FilterRules>(source).map_err(|e| e.to_string())?;
+
+		Self::new(&rules)
+	}
+
+	/// Re-reads the rules file if it has changed since it was last read.
+	/// Returns `Ok(true)` if the rules were replaced. If the new rules are
+	/// invalid the current ones are kept.
+	pub fn reload_if_changed(&mut self, path: &str) -> Result<bool, String> {
+		let modified = modified_time(path);
+
+		if modified.is_none() || modified == self.modified {
+			return Ok(false);
+		}
+
+		// Record the attempt even if it fails, so a broken file is only reported
+		// once.
+		self.modified = modified;
+		*self = Self::initialize(path)?;
+
+		Ok(true)
+	}
+
+	/// Checks `content` against every rule.
+	pub fn check(&self, content: &str) -> FilterResult {
+		let mut hits = Vec::new();
+		let mut redacted = content.to_owned();
+
+		for rule in &self.rules {
+			let m = match rule.pattern.find(content) {
+				Some(m) => m,
+				None => continue,
+			};
+
+			hits.push(FilterHit {
+				rule: rule.name.to_owned(),
+				action: rule.action,
+				matched: m.as_str().to_owned(),
+			});
+
+			if rule.action == FilterAction::Redact {
+				redacted = rule.pattern.replace_all(&redacted, REDACTED).into_owned();
+			}
+		}
+
+		let verdict = if hits.iter().any(|h| h.action == FilterAction::Drop) {
+			FilterVerdict::Drop
+		} else if redacted != content {
+			FilterVerdict::Redact(redacted)
+		} else {
+			FilterVerdict::Pass
+		};
+
+		FilterResult { verdict, hits }
+	}
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn rule(name: &str, action: FilterAction, words: &[&str], regex: Option<&str>) -> FilterRule {
+		FilterRule {
+			name: name.to_owned(),
+			action,
+			words: words.iter().map(|w| w.to_string()).collect(),
+			regex: regex.map(str::to_owned),
+		}
+	}
+
+	fn filter(block_invites: Option<FilterAction>, rules: Vec<FilterRule>) -> ContentFilter {
+		ContentFilter::new(&FilterRules {
+			block_invites,
+			rules,
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn words_only_match_on_word_boundaries() {
+		let filter = filter(
+			None,
+			vec![rule("words", FilterAction::Drop, &["ass"], None)],
+		);
+
+		assert_eq!(
+			filter.check("What a pain in the ASS").verdict,
+			FilterVerdict::Drop
+		);
+		assert_eq!(
+			filter.check("I'll pass, classic assumption").verdict,
+			FilterVerdict::Pass
+		);
+		assert!(filter.check("Nothing to see").hits.is_empty());
+	}
+
+	#[test]
+	fn regex_rules_match_and_report_the_matched_text() {
+		let filter = filter(
+			None,
+			vec![rule(
+				"nitro",
+				FilterAction::Flag,
+				&[],
+				Some(r"free\s+nitro"),
+			)],
+		);
+		let result = filter.check("Get FREE   nitro here");
+
+		assert_eq!(result.verdict, FilterVerdict::Pass);
+		assert_eq!(result.hits.len(), 1);
+		assert_eq!(result.hits[0].rule, "nitro");
+		assert_eq!(result.hits[0].matched, "FREE   nitro");
+	}
+
+	#[test]
+	fn invite_links_are_blocked() {
+		let filter = filter(Some(FilterAction::Redact), Vec::new());
+
+		for invite in [
+			"discord.gg/abc-123",
+			"https://discord.com/invite/abc",
+			"http://www.discordapp.com/invite/abc",
+		] {
+			assert_eq!(
+				filter.check(&format!("Join {invite} now")).verdict,
+				FilterVerdict::Redact("Join [redacted] now".to_owned()),
+				"{invite}"
+			);
+		}
+
+		assert_eq!(
+			filter.check("https://discord.com/channels/1/2").verdict,
+			FilterVerdict::Pass
+		);
+	}
+
+	#[test]
+	fn drop_takes_precedence_over_redact() {
+		let filter = filter(
+			None,
+			vec![
+				rule("redacted", FilterAction::Redact, &["darn"], None),
+				rule("dropped", FilterAction::Drop, &["spam"], None),
+			],
+		);
+		let result = filter.check("darn spam");
+
+		assert_eq!(result.verdict, FilterVerdict::Drop);
+		assert_eq!(result.hits.len(), 2);
+	}
+
+	#[test]
+	fn every_match_is_redacted() {
+		let filter = filter(
+			None,
+			vec![
+				rule("words", FilterAction::Redact, &["darn", "heck"], None),
+				rule("digits", FilterAction::Redact, &[], Some(r"\d{4}")),
+			],
+		);
+
+		assert_eq!(
+			filter.check("Darn, heck, darn! My pin is 1234").verdict,
+			FilterVerdict::Redact(
+				"[redacted], [redacted], [redacted]! My pin is [redacted]".to_owned()
+			)
+		);
+	}
+
+	#[test]
+	fn invalid_rules_are_rejected() {
+		let both = rule("both", FilterAction::Drop, &["a"], Some("a"));
+		let neither = rule("neither", FilterAction::Drop, &[], None);
+		let invalid = rule("invalid", FilterAction::Drop, &[], Some("(unclosed"));
+		let empty = rule("empty", FilterAction::Drop, &["spam", " "], None);
+
+		for (rule, error) in [
+			(both, "must have either `words` or `regex`"),
+			(neither, "must have either `words` or `regex`"),
+			(invalid, "has an invalid pattern"),
+			(empty, "has an empty word"),
+		] {
+			let name = rule.name.to_owned();
+			let result = ContentFilter::new(&FilterRules {
+				block_invites: None,
+				rules: vec![rule],
+			});
+
+			assert!(result.unwrap_err().contains(error), "{name}");
+		}
+	}
+}
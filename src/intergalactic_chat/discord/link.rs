@@ -0,0 +1,341 @@
+use std::process::exit;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::http::Http;
+use serenity::model::Timestamp;
+use tokio::sync::watch;
+use tokio::time::sleep;
+use tokio::{signal, task};
+
+use super::bans::lift_expired_bans;
+use super::bot::DiscordHandler;
+use super::deletions;
+use super::edits;
+use super::filter::FilterVerdict;
+use super::mod_log::ModLogEvent;
+use super::rate_limit::{PeerKey, RateLimitResult};
+use super::send_queue::SendQueue;
+use crate::intergalactic_chat::metrics::{DropReason, LinkMetrics};
+
+/// How often expired bans are lifted.
+const BAN_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tracks whether the bot has been ready yet. Serenity sends a ready event
+/// every time it reconnects to the gateway, but the link is only set up once.
+#[derive(Debug)]
+pub struct Startup {
+	ready: watch::Sender<bool>,
+}
+
+impl Default for Startup {
+	fn default() -> Self {
+		Self {
+			ready: watch::channel(false).0,
+		}
+	}
+}
+
+impl Startup {
+	/// Records a ready event, returning `true` for the first one only.
+	pub fn ready(&self) -> bool {
+		!self.ready.send_replace(true)
+	}
+
+	/// Waits for the first ready event.
+	pub async fn wait(&self) {
+		let mut ready = self.ready.subscribe();
+
+		while !*ready.borrow() {
+			if ready.changed().await.is_err() {
+				return;
+			}
+		}
+	}
+}
+
+/// Starts the tasks that run for as long as the bot does: saving the bot's
+/// state on Ctrl-C, lifting expired bans and mirroring the messages received
+/// from other bots. Called once, before connecting to Discord.
+pub fn start(handler: Arc<DiscordHandler>, http: Arc<Http>) {
+	let saved_handler = Arc::clone(&handler);
+
+	task::spawn(async move {
+		loop {
+			match signal::ctrl_c().await {
+				Ok(()) => {
+					let data_dir = &saved_handler.data_dir;
+					let saved = [
+						saved_handler
+							.message_cache
+							.lock()
+							.await
+							.snapshot(&data_dir.cache())
+							.map(|_| ()),
+						saved_handler
+							.ban_list
+							.lock()
+							.await
+							.write_to_file(&data_dir.bans()),
+						saved_handler
+							.mute_list
+							.lock()
+							.await
+							.write_to_file(&data_dir.mutes()),
+					];
+
+					for e in saved.into_iter().filter_map(Result::err) {
+						eprintln!("Unable to save the bot's state: {e}");
+					}
+
+					println!("Goodbye!");
+
+					exit(0)
+				}
+				Err(e) => {
+					eprintln!("Unable to listen for shutdown signal: {e}");
+				}
+			}
+		}
+	});
+
+	let sweeper_handler = Arc::clone(&handler);
+	let sweeper_http = Arc::clone(&http);
+
+	task::spawn(async move {
+		// Lifted bans are posted to the mod-logs, which are found when the bot
+		// is ready.
+		sweeper_handler.startup.wait().await;
+
+		loop {
+			lift_expired_bans(
+				&sweeper_http,
+				&sweeper_handler.ban_list,
+				&sweeper_handler.data_dir.bans(),
+				&sweeper_handler.mod_log,
+				Timestamp::now(),
+			)
+			.await;
+			sleep(BAN_SWEEP_INTERVAL).await;
+		}
+	});
+
+	task::spawn(receive(handler, http));
+}
+
+/// Mirrors the messages received from other bots into every linked channel,
+/// and applies the edits and deletions published by bots on the network.
+/// Waits for the bot to be ready, so the webhooks have been set up.
+pub async fn receive(handler: Arc<DiscordHandler>, http: Arc<Http>) {
+	handler.startup.wait().await;
+
+	// Process the event and ensure it's a valid Discord message. The loop
+	// will simply return if a problem is found, as none of the issues are
+	// unrecoverable.
+	// TODO: This can definitely be done more efficiently!
+	let max_concurrent_sends = handler.config.read().await.discord.max_concurrent_sends;
+	let mut send_queue = SendQueue::new(&handler, Arc::clone(&http), max_concurrent_sends);
+	let mut chat = handler.mq_chat.lock().await;
+	let mut edits = handler.mq_edits.lock().await;
+	let mut mirror_deletions = handler.mq_deletions.lock().await;
+
+	loop {
+		let mut message = tokio::select! {
+			Some(edit) = edits.recv() => {
+				edits::apply(&handler, &http, edit).await;
+				continue;
+			}
+			Some(deletion) = mirror_deletions.recv() => {
+				deletions::apply(&handler, &http, deletion).await;
+				continue;
+			}
+			Some(message) = chat.recv() => message,
+			else => return,
+		};
+
+		LinkMetrics::increment(&handler.metrics.messages_received);
+
+		// The broker may deliver a message more than once.
+		if handler
+			.message_cache
+			.lock()
+			.await
+			.get_entry(&message.id)
+			.is_some()
+		{
+			handler.metrics.dropped(DropReason::Duplicate);
+			continue;
+		}
+
+		if let Some(guild_id) = message.guild_id {
+			handler.link_status.lock().await.saw_peer(guild_id);
+
+			// Messages don't carry the bot that published them, so it is looked
+			// up from the guilds listed in the heartbeats.
+			let peer = handler
+				.peer_directory
+				.lock()
+				.await
+				.client_for_guild(guild_id)
+				.map(str::to_owned);
+			let key = match &peer {
+				Some(client_id) => PeerKey::Client(client_id.to_owned()),
+				None => PeerKey::Guild(guild_id),
+			};
+			let limited = handler.rate_limits.lock().await.peers.check(key);
+
+			if let RateLimitResult::Limited { first } = limited {
+				if first {
+					match &peer {
+						Some(client_id) => {
+							println!("Rate limiting messages received from {client_id}")
+						}
+						None => println!("Rate limiting messages received from {guild_id}"),
+					}
+
+					handler
+						.mod_log
+						.lock()
+						.await
+						.post_all(
+							&http,
+							ModLogEvent::PeerRateLimited {
+								origin: guild_id,
+								peer,
+							},
+						)
+						.await;
+				}
+
+				handler.metrics.dropped(DropReason::RateLimited);
+				continue;
+			}
+		}
+
+		let filter_received = handler.config.read().await.filter.filter_received;
+
+		if filter_received {
+			let result = handler.content_filter.lock().await.check(&message.content);
+
+			for hit in &result.hits {
+				handler
+					.mod_log
+					.lock()
+					.await
+					.post_all(&http, ModLogEvent::filter_hit(&message, hit, true))
+					.await;
+			}
+
+			match result.verdict {
+				FilterVerdict::Drop => {
+					handler.metrics.dropped(DropReason::Filtered);
+					continue;
+				}
+				FilterVerdict::Redact(content) => message.content = content,
+				FilterVerdict::Pass => (),
+			}
+		}
+
+		handler
+			.message_cache
+			.lock()
+			.await
+			.push(message.id, message.channel_id, Vec::new());
+
+		let mut webhooks = handler.webhooks.lock().await.to_owned();
+
+		// The mute list isn't held while queueing, as that can wait for a
+		// channel that is behind.
+		{
+			let mute_list = handler.mute_list.lock().await;
+
+			webhooks.retain(|w| match w.guild_id {
+				Some(g) => !mute_list.is_muted(g, message.author.id, message.guild_id),
+				None => true,
+			});
+		}
+
+		for webhook in webhooks {
+			send_queue.push(message.to_owned(), webhook).await;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serenity::model::prelude::{GuildId, MessageId, UserId};
+
+	use super::*;
+	use crate::intergalactic_chat::discord::bans::BanEntry;
+	use crate::intergalactic_chat::harness::broker::Broker;
+	use crate::intergalactic_chat::harness::discord::{message, user};
+	use crate::intergalactic_chat::harness::{eventually, Bot};
+
+	const CHANNEL_A: u64 = 2_100_000_000_000_000_001;
+	const GUILD_A: u64 = 2_200_000_000_000_000_001;
+	const CHANNEL_B: u64 = 2_100_000_000_000_000_002;
+	const GUILD_B: u64 = 2_200_000_000_000_000_002;
+	const ALICE: u64 = 2_300_000_000_000_000_001;
+
+	/// A ban on `user` that expired long ago.
+	fn expired_ban() -> BanEntry {
+		BanEntry {
+			reason: "Spam".to_owned(),
+			executor: UserId(ALICE),
+			ban_origin: GuildId(GUILD_A),
+			timestamp: Timestamp::from_unix_timestamp(0).unwrap(),
+			expires_at: Some(Timestamp::from_unix_timestamp(60).unwrap()),
+		}
+	}
+
+	#[tokio::test]
+	async fn a_second_ready_does_not_restart_the_link() {
+		let broker = Broker::start().await;
+		let bot = Bot::start(
+			&broker,
+			"link-a",
+			&[(CHANNEL_A, GUILD_A), (CHANNEL_B, GUILD_B)],
+		)
+		.await;
+		let ban_list = &bot.handler.ban_list;
+
+		ban_list.lock().await.list.insert(UserId(1), expired_ban());
+		bot.ready().await;
+
+		eventually("The first sweep never ran", || async {
+			ban_list.lock().await.list.is_empty().then_some(())
+		})
+		.await;
+
+		// Serenity sends a ready event on every reconnect. A sweeper started by
+		// this one would lift the ban straight away, rather than after
+		// `BAN_SWEEP_INTERVAL`.
+		ban_list.lock().await.list.insert(UserId(2), expired_ban());
+		bot.ready().await;
+
+		let id = 2_400_000_000_000_000_001;
+
+		bot.send(message(id, CHANNEL_A, user(ALICE, "Alice", false), "Hello"))
+			.await;
+		bot.mirrored(id, 1).await;
+		sleep(Duration::from_millis(200)).await;
+
+		let executions = bot
+			.discord
+			.requests()
+			.await
+			.into_iter()
+			.filter(|r| r.is_execution())
+			.count();
+
+		assert_eq!(executions, 1);
+		assert!(ban_list.lock().await.list.contains_key(&UserId(2)));
+		assert!(bot
+			.handler
+			.message_cache
+			.lock()
+			.await
+			.get_entry(&MessageId(id))
+			.is_some());
+	}
+}
@@ -1,5 +1,17 @@
+pub mod bans;
 pub mod bot;
-pub mod util;
 pub mod cache;
-pub mod bans;
 pub mod commands;
+pub mod deletions;
+pub mod edits;
+pub mod filter;
+pub mod link;
+pub mod mod_log;
+pub mod mutes;
+pub mod permissions;
+pub mod rate_limit;
+pub mod registration;
+pub mod send_queue;
+pub mod status;
+pub mod util;
+pub mod webhooks;
@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use serenity::{
+	builder::CreateEmbed,
+	http::CacheHttp,
+	model::{
+		prelude::{ChannelId, GuildId, Message, MessageId, User, UserId},
+		Timestamp,
+	},
+	utils::Colour,
+};
+
+use super::{
+	bans::BanEntry,
+	filter::{FilterAction, FilterHit},
+	mutes::MuteEntry,
+};
+
+/// Something moderators should know about, posted to the mod-log channels as
+/// an embed.
+pub enum ModLogEvent<'a> {
+	Ban {
+		user: &'a User,
+		entry: &'a BanEntry,
+	},
+	Unban {
+		user: &'a User,
+		executor: UserId,
+		origin: GuildId,
+		/// The ban that was lifted.
+		entry: &'a BanEntry,
+	},
+	/// A timed ban was lifted by the bot.
+	BanExpired {
+		user: UserId,
+		entry: &'a BanEntry,
+	},
+	/// Bans were imported with `/network-bans import`.
+	BansImported {
+		executor: UserId,
+		origin: GuildId,
+		added: usize,
+		skipped: usize,
+	},
+	/// A user or server was muted on one server.
+	Muted {
+		target: String,
+		entry: &'a MuteEntry,
+	},
+	Unmuted {
+		target: String,
+		executor: UserId,
+	},
+	FilterHit {
+		/// The message's author, edits don't always include it.
+		author: Option<&'a User>,
+		channel: ChannelId,
+		guild: Option<GuildId>,
+		message: MessageId,
+		hit: &'a FilterHit,
+		/// Whether the message was received from another server rather than
+		/// sent in a linked channel.
+		received: bool,
+		/// Whether the rule matched an edit of the message.
+		edited: bool,
+	},
+	/// A user or linked channel tripped a rate limit, so their messages are not
+	/// being propagated.
+	RateLimited {
+		user: UserId,
+		channel: ChannelId,
+	},
+	/// Messages received from another bot tripped a rate limit. `peer` is the
+	/// MQTT client ID of the bot, if its heartbeat has been seen.
+	PeerRateLimited {
+		origin: GuildId,
+		peer: Option<String>,
+	},
+	/// A message was deleted on another server, so its copy here was deleted
+	/// too.
+	MirrorDeleted {
+		origin_channel: ChannelId,
+		origin_guild: Option<GuildId>,
+		origin_message: MessageId,
+		mirror_channel: ChannelId,
+	},
+	/// A moderator deleted a copy of a message on another server, so the
+	/// message in `channel` was deleted too.
+	DeletionPropagated {
+		deleted_in: Option<GuildId>,
+		origin_message: MessageId,
+		channel: ChannelId,
+	},
+	/// Messages can't be posted in a linked channel.
+	WebhookDegraded {
+		channel: ChannelId,
+		reason: String,
+	},
+	WebhookRecovered {
+		channel: ChannelId,
+	},
+}
+
+impl<'a> ModLogEvent<'a> {
+	/// A [`ModLogEvent::FilterHit`] for a message, rather than an edit.
+	pub fn filter_hit(message: &'a Message, hit: &'a FilterHit, received: bool) -> Self {
+		Self::FilterHit {
+			author: Some(&message.author),
+			channel: message.channel_id,
+			guild: message.guild_id,
+			message: message.id,
+			hit,
+			received,
+			edited: false,
+		}
+	}
+
+	fn build(&self, e: &mut CreateEmbed) {
+		match self {
+			Self::Ban { user, entry } => e
+				.title("Network ban")
+				.colour(Colour::RED)
+				.description(format!("<@{}> was banned from the chat link.", user.id))
+				.field("User", format!("{} ({})", user.tag(), user.id), true)
+				.field("Executor", format!("<@{}>", entry.executor), true)
+				.field("Origin server", entry.display_origin(), true)
+				.field("Expires", entry.display_expiry(), true)
+				.field("Reason", &entry.reason, false)
+				.timestamp(entry.timestamp),
+			Self::Unban {
+				user,
+				executor,
+				origin,
+				entry,
+			} => e
+				.title("Network unban")
+				.colour(Colour::DARK_GREEN)
+				.description(format!("<@{}> was unbanned from the chat link.", user.id))
+				.field("User", format!("{} ({})", user.tag(), user.id), true)
+				.field("Executor", format!("<@{executor}>"), true)
+				.field("Origin server", origin, true)
+				.field(
+					"Original ban",
+					format!(
+						"\"{}\" by <@{}> in {}",
+						entry.reason,
+						entry.executor,
+						entry.display_origin()
+					),
+					false,
+				)
+				.timestamp(Timestamp::now()),
+			Self::BanExpired { user, entry } => e
+				.title("Network ban expired")
+				.colour(Colour::DARK_GREEN)
+				.description(format!("The ban for <@{user}> has expired."))
+				.field("User", user, true)
+				.field("Executor", format!("<@{}>", entry.executor), true)
+				.field("Origin server", entry.display_origin(), true)
+				.field("Reason", &entry.reason, false)
+				.timestamp(Timestamp::now()),
+			Self::BansImported {
+				executor,
+				origin,
+				added,
+				skipped,
+			} => e
+				.title("Network bans imported")
+				.colour(Colour::RED)
+				.description(format!(
+					"{added} users were banned from the chat link by an import, {skipped} were skipped."
+				))
+				.field("Executor", format!("<@{executor}>"), true)
+				.field("Origin server", origin, true)
+				.timestamp(Timestamp::now()),
+			Self::Muted { target, entry } => e
+				.title("Muted")
+				.colour(Colour::ORANGE)
+				.description(format!(
+					"Messages from {target} will no longer be shown on this server."
+				))
+				.field("Executor", format!("<@{}>", entry.executor), true)
+				.field("Reason", &entry.reason, false)
+				.timestamp(entry.timestamp),
+			Self::Unmuted { target, executor } => e
+				.title("Unmuted")
+				.colour(Colour::DARK_GREEN)
+				.description(format!("Messages from {target} will be shown on this server again."))
+				.field("Executor", format!("<@{executor}>"), true)
+				.timestamp(Timestamp::now()),
+			Self::FilterHit {
+				author,
+				channel,
+				guild,
+				message,
+				hit,
+				received,
+				edited,
+			} => e
+				.title("Filter hit")
+				.colour(Colour::ORANGE)
+				.description(format!(
+					"{} {} <#{channel}> matched the rule \"{}\" and {} {}.\n{}",
+					if *edited { "An edit of a message" } else { "A message" },
+					if *received { "received from" } else { "sent in" },
+					hit.rule,
+					if *edited { "the edit was" } else { "was" },
+					match hit.action {
+						FilterAction::Drop => "dropped",
+						FilterAction::Redact => "redacted",
+						FilterAction::Flag => "flagged",
+					},
+					message.link(*channel, *guild)
+				))
+				.field(
+					"Author",
+					match author {
+						Some(a) => format!("{} ({})", a.tag(), a.id),
+						None => "Unknown".to_owned(),
+					},
+					true,
+				)
+				.field("Origin server", display_guild(*guild), true)
+				.field("Matched", format!("`{}`", hit.matched.replace('`', "'")), false)
+				.timestamp(Timestamp::now()),
+			Self::RateLimited { user, channel } => e
+				.title("Rate limited")
+				.colour(Colour::GOLD)
+				.description(format!(
+					"Messages from <@{user}> in <#{channel}> are being sent too quickly and are no longer being propagated."
+				))
+				.timestamp(Timestamp::now()),
+			Self::PeerRateLimited { origin, peer } => {
+				e.title("Rate limited")
+					.colour(Colour::GOLD)
+					.description("Messages received from another bot are arriving too quickly and are no longer being shown here.")
+					.field("Origin server", origin, true)
+					.timestamp(Timestamp::now());
+
+				if let Some(peer) = peer {
+					e.field("Origin bot", format!("`{peer}`"), true);
+				}
+
+				e
+			}
+			Self::MirrorDeleted {
+				origin_channel,
+				origin_guild,
+				origin_message,
+				mirror_channel,
+			} => e
+				.title("Message deleted")
+				.colour(Colour::BLURPLE)
+				.description(format!(
+					"A message was deleted on another server, so its copy in <#{mirror_channel}> was deleted too."
+				))
+				.field("Origin server", display_guild(*origin_guild), true)
+				.field("Origin channel", format!("<#{origin_channel}>"), true)
+				.field("Origin message", origin_message, true)
+				.timestamp(Timestamp::now()),
+			Self::DeletionPropagated {
+				deleted_in,
+				origin_message,
+				channel,
+			} => e
+				.title("Message deleted")
+				.colour(Colour::BLURPLE)
+				.description(format!(
+					"A moderator on another server deleted a copy of a message, so the message in <#{channel}> was deleted too."
+				))
+				.field("Deleted in", display_guild(*deleted_in), true)
+				.field("Origin message", origin_message, true)
+				.timestamp(Timestamp::now()),
+			Self::WebhookDegraded { channel, reason } => e
+				.title("Linked channel degraded")
+				.colour(Colour::ORANGE)
+				.description(format!(
+					"Messages from other servers can't be posted in <#{channel}>. The bot will keep trying, and post here once it's fixed."
+				))
+				.field("Reason", reason, false)
+				.timestamp(Timestamp::now()),
+			Self::WebhookRecovered { channel } => e
+				.title("Linked channel recovered")
+				.colour(Colour::DARK_GREEN)
+				.description(format!(
+					"Messages from other servers are being posted in <#{channel}> again."
+				))
+				.timestamp(Timestamp::now()),
+		};
+	}
+}
+
+/// Tracks the mod-log channel of each guild, along with the guild each linked
+/// channel belongs to so events can be routed to the right server.
+#[derive(Debug, Clone, Default)]
+pub struct ModLog {
+	log_channels: HashMap<GuildId, ChannelId>,
+	channel_guilds: HashMap<ChannelId, GuildId>,
+}
+
+impl ModLog {
+	/// Looks up the guild of every mod-log and linked channel. Channels that
+	/// can't be found are skipped.
+	pub async fn resolve(
+		cache_http: impl CacheHttp, log_channels: &[u64], linked_channels: &[u64],
+	) -> Self {
+		let mut mod_log = Self::default();
+
+		for channel in linked_channels {
+			if let Some(guild) = guild_of(&cache_http, ChannelId::from(*channel)).await {
+				mod_log
+					.channel_guilds
+					.insert(ChannelId::from(*channel), guild);
+			}
+		}
+
+		for channel in log_channels {
+			let channel = ChannelId::from(*channel);
+
+			match guild_of(&cache_http, channel).await {
+				Some(guild) => {
+					if let Some(existing) = mod_log.log_channels.insert(guild, channel) {
+						println!("Both {existing} and {channel} are mod-log channels for {guild}, only {channel} will be used");
+					}
+				}
+				None => println!("Unable to find the mod-log channel {channel}"),
+			}
+		}
+
+		mod_log
+	}
+
+	/// The guild a linked channel belongs to.
+	pub fn guild_of(&self, channel: ChannelId) -> Option<GuildId> {
+		self.channel_guilds.get(&channel).copied()
+	}
+
+	/// Posts `event` to the mod-log channel of `guild`, if it has one.
+	pub async fn post(
+		&self, cache_http: impl CacheHttp, guild: Option<GuildId>, event: ModLogEvent<'_>,
+	) {
+		let channel = match guild.and_then(|g| self.log_channels.get(&g)) {
+			Some(c) => *c,
+			None => return,
+		};
+
+		send(&cache_http, channel, &event).await;
+	}
+
+	/// Posts `event` to every mod-log channel, used for network wide events.
+	pub async fn post_all(&self, cache_http: impl CacheHttp, event: ModLogEvent<'_>) {
+		for channel in self.log_channels.values() {
+			send(&cache_http, *channel, &event).await;
+		}
+	}
+}
+
+async fn send(cache_http: impl CacheHttp, channel: ChannelId, event: &ModLogEvent<'_>) {
+	if let Err(e) = channel
+		.send_message(cache_http.http(), |m| {
+			m.embed(|e| {
+				event.build(e);
+				e
+			})
+		})
+		.await
+	{
+		println!("Error posting to the mod-log channel {channel}: {e}");
+	}
+}
+
+async fn guild_of(cache_http: impl CacheHttp, channel: ChannelId) -> Option<GuildId> {
+	channel
+		.to_channel(cache_http)
+		.await
+		.ok()
+		.and_then(|c| c.guild())
+		.map(|c| c.guild_id)
+}
+
+fn display_guild(guild: Option<GuildId>) -> String {
+	match guild {
+		Some(g) => g.to_string(),
+		None => "Unknown".to_owned(),
+	}
+}
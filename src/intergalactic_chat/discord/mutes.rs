@@ -0,0 +1,69 @@
+use std::{collections::HashMap, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::{
+	prelude::{GuildId, UserId},
+	Timestamp,
+};
+
+use crate::intergalactic_chat::data::{read_state, write_state};
+
+/// Users and servers muted by each guild. Unlike network bans, mutes only stop
+/// messages from appearing in the guild that muted them.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MuteList {
+	pub list: HashMap<GuildId, GuildMutes>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GuildMutes {
+	/// Users whose messages are not shown in the guild.
+	#[serde(default)]
+	pub users: HashMap<UserId, MuteEntry>,
+	/// Guilds whose messages are not shown in the guild.
+	#[serde(default)]
+	pub guilds: HashMap<GuildId, MuteEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MuteEntry {
+	pub reason: String,
+	pub executor: UserId,
+	pub timestamp: Timestamp,
+}
+
+impl MuteList {
+	pub fn new() -> Self {
+		MuteList {
+			list: HashMap::new(),
+		}
+	}
+
+	/// Reads the state saved at `path`, recovering from the backup if it's
+	/// corrupt and starting empty if there is nothing to read.
+	pub fn initialize(path: &Path) -> Self {
+		read_state(path).unwrap_or_else(Self::new)
+	}
+
+	/// Whether a message from `author`, sent in `origin`, should be hidden from
+	/// `guild`.
+	pub fn is_muted(&self, guild: GuildId, author: UserId, origin: Option<GuildId>) -> bool {
+		match self.list.get(&guild) {
+			Some(m) => {
+				m.users.contains_key(&author) || origin.is_some_and(|o| m.guilds.contains_key(&o))
+			}
+			None => false,
+		}
+	}
+
+	/// The mutes of `guild`, creating an empty set if it has none.
+	pub fn guild(&mut self, guild: GuildId) -> &mut GuildMutes {
+		self.list.entry(guild).or_default()
+	}
+
+	/// Writes [`MuteList`] to the file provided by `path`, keeping the previous
+	/// version as a backup.
+	pub fn write_to_file(&self, path: &Path) -> Result<(), io::Error> {
+		write_state(path, self)
+	}
+}
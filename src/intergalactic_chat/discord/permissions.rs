@@ -0,0 +1,157 @@
+use serenity::model::prelude::{Member, UserId};
+
+use crate::intergalactic_chat::config::{PermissionTier, Permissions};
+
+/// The tier of a user, based on the network owners and the roles of `member`.
+pub fn tier_of(permissions: &Permissions, user: UserId, member: Option<&Member>) -> PermissionTier {
+	if permissions.owners.contains(user.as_u64()) {
+		PermissionTier::Owner
+	} else if member.is_some_and(|m| {
+		m.roles
+			.iter()
+			.any(|r| permissions.moderator_roles.contains(r.as_u64()))
+	}) {
+		PermissionTier::Moderator
+	} else {
+		PermissionTier::Everyone
+	}
+}
+
+/// The lowest tier that can use `command`.
+pub fn required_tier(permissions: &Permissions, command: &str) -> PermissionTier {
+	permissions
+		.commands
+		.get(command)
+		.copied()
+		.unwrap_or(PermissionTier::Everyone)
+}
+
+/// Whether `user` is allowed to use `command`.
+pub fn is_allowed(
+	permissions: &Permissions, command: &str, user: UserId, member: Option<&Member>,
+) -> bool {
+	tier_of(permissions, user, member) >= required_tier(permissions, command)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::*;
+
+	const OWNER: UserId = UserId(1);
+	const MEMBER: UserId = UserId(2);
+	const MODERATOR_ROLE: u64 = 10;
+
+	fn permissions() -> Permissions {
+		Permissions {
+			owners: vec![OWNER.0],
+			moderator_roles: vec![MODERATOR_ROLE],
+			commands: HashMap::from([
+				("network-ban".to_owned(), PermissionTier::Moderator),
+				("link-owner-only".to_owned(), PermissionTier::Owner),
+			]),
+		}
+	}
+
+	/// A member of a server with `roles`.
+	fn member(user: UserId, roles: &[u64]) -> Member {
+		serde_json::from_value(serde_json::json!({
+			"user": {
+				"id": user.to_string(),
+				"username": "Someone",
+				"discriminator": "0001",
+				"avatar": null,
+			},
+			"roles": roles.iter().map(u64::to_string).collect::<Vec<_>>(),
+			"joined_at": "2023-04-01T12:00:00.000000+00:00",
+			"deaf": false,
+			"mute": false,
+			"guild_id": "100",
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn tiers_are_ordered() {
+		assert!(PermissionTier::Everyone < PermissionTier::Moderator);
+		assert!(PermissionTier::Moderator < PermissionTier::Owner);
+	}
+
+	#[test]
+	fn owners_can_use_every_command_without_a_role() {
+		let permissions = permissions();
+		let owner = member(OWNER, &[]);
+
+		assert_eq!(
+			tier_of(&permissions, OWNER, Some(&owner)),
+			PermissionTier::Owner
+		);
+		// Commands used in direct messages have no member.
+		assert_eq!(tier_of(&permissions, OWNER, None), PermissionTier::Owner);
+
+		for command in ["network-ban", "link-owner-only", "ping"] {
+			assert!(
+				is_allowed(&permissions, command, OWNER, Some(&owner)),
+				"{command}"
+			);
+		}
+	}
+
+	#[test]
+	fn moderator_roles_grant_the_moderator_tier() {
+		let permissions = permissions();
+		let moderator = member(MEMBER, &[5, MODERATOR_ROLE]);
+
+		assert_eq!(
+			tier_of(&permissions, MEMBER, Some(&moderator)),
+			PermissionTier::Moderator
+		);
+		assert!(is_allowed(
+			&permissions,
+			"network-ban",
+			MEMBER,
+			Some(&moderator)
+		));
+		assert!(!is_allowed(
+			&permissions,
+			"link-owner-only",
+			MEMBER,
+			Some(&moderator)
+		));
+	}
+
+	#[test]
+	fn members_without_a_role_are_everyone() {
+		let permissions = permissions();
+		let member = member(MEMBER, &[5]);
+
+		assert_eq!(
+			tier_of(&permissions, MEMBER, Some(&member)),
+			PermissionTier::Everyone
+		);
+		assert!(!is_allowed(
+			&permissions,
+			"network-ban",
+			MEMBER,
+			Some(&member)
+		));
+	}
+
+	#[test]
+	fn unlisted_commands_can_be_used_by_everyone() {
+		let permissions = permissions();
+
+		assert_eq!(
+			required_tier(&permissions, "ping"),
+			PermissionTier::Everyone
+		);
+		assert!(is_allowed(&permissions, "ping", MEMBER, None));
+		assert!(is_allowed(
+			&Permissions::default(),
+			"network-ban",
+			MEMBER,
+			None
+		));
+	}
+}
@@ -0,0 +1,219 @@
+use std::{collections::HashMap, hash::Hash, time::Instant};
+
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+use crate::intergalactic_chat::config::{Limit, RateLimit};
+
+/// The number of tracked keys after which full buckets are forgotten.
+const PRUNE_THRESHOLD: usize = 1000;
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+	tokens: f64,
+	last_refill: Instant,
+	/// Whether the key has been told it is being limited since it last sent a
+	/// message successfully.
+	notified: bool,
+}
+
+/// The result of [`RateLimiter::check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitResult {
+	Allowed,
+	/// The key has run out of tokens. `first` is `true` only for the first
+	/// limited message, so a notice is only sent once per burst.
+	Limited {
+		first: bool,
+	},
+}
+
+/// Token bucket rate limiter, tracking a separate bucket for every key.
+#[derive(Debug, Clone)]
+pub struct RateLimiter<K> {
+	limit: Limit,
+	buckets: HashMap<K, TokenBucket>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+	pub fn new(limit: Limit) -> Self {
+		Self {
+			limit,
+			buckets: HashMap::new(),
+		}
+	}
+
+	/// Takes a token from the bucket for `key` if one is available.
+	pub fn check(&mut self, key: K) -> RateLimitResult {
+		self.check_at(key, Instant::now())
+	}
+
+	pub fn check_at(&mut self, key: K, now: Instant) -> RateLimitResult {
+		let bucket = match self.refill(key, now) {
+			Some(b) => b,
+			None => return RateLimitResult::Allowed,
+		};
+
+		if bucket.tokens >= 1.0 {
+			bucket.tokens -= 1.0;
+			bucket.notified = false;
+
+			RateLimitResult::Allowed
+		} else {
+			let first = !bucket.notified;
+			bucket.notified = true;
+
+			RateLimitResult::Limited { first }
+		}
+	}
+
+	/// Whether the bucket for `key` has a token, without taking it.
+	pub fn available_at(&mut self, key: K, now: Instant) -> bool {
+		self.refill(key, now).is_none_or(|b| b.tokens >= 1.0)
+	}
+
+	/// Refills the bucket for `key` up to `now`, `None` if the limit is
+	/// disabled.
+	fn refill(&mut self, key: K, now: Instant) -> Option<&mut TokenBucket> {
+		if self.limit.messages == 0 {
+			return None;
+		}
+
+		let capacity = f64::from(self.limit.messages);
+		let refill_rate = capacity / self.limit.seconds.max(1) as f64;
+
+		if self.buckets.len() > PRUNE_THRESHOLD {
+			self.buckets.retain(|_, b| {
+				b.tokens + now.duration_since(b.last_refill).as_secs_f64() * refill_rate < capacity
+			});
+		}
+
+		let bucket = self.buckets.entry(key).or_insert(TokenBucket {
+			tokens: capacity,
+			last_refill: now,
+			notified: false,
+		});
+
+		bucket.tokens = (bucket.tokens
+			+ now.duration_since(bucket.last_refill).as_secs_f64() * refill_rate)
+			.min(capacity);
+		bucket.last_refill = now;
+
+		Some(bucket)
+	}
+}
+
+/// The key of the receive-side limit: the MQTT client ID of the bot that
+/// published the message, or the guild it was sent in if no heartbeat from
+/// that bot lists the guild.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PeerKey {
+	Client(String),
+	Guild(GuildId),
+}
+
+/// Every rate limiter used by the bot.
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+	/// Limits messages sent by each user before they are published.
+	pub users: RateLimiter<UserId>,
+	/// Limits messages sent in each linked channel before they are published.
+	pub channels: RateLimiter<ChannelId>,
+	/// Limits messages received from each bot on the network.
+	pub peers: RateLimiter<PeerKey>,
+}
+
+impl RateLimits {
+	pub fn new(config: &RateLimit) -> Self {
+		Self {
+			users: RateLimiter::new(config.user),
+			channels: RateLimiter::new(config.channel),
+			peers: RateLimiter::new(config.peer),
+		}
+	}
+
+	/// Checks a message sent by `user` in `channel` against both limits. A
+	/// token is only taken from either bucket when both have one, so messages
+	/// dropped by the channel limit don't count against the user.
+	pub fn check_sent(&mut self, user: UserId, channel: ChannelId) -> RateLimitResult {
+		let now = Instant::now();
+
+		if !self.users.available_at(user, now) {
+			return self.users.check_at(user, now);
+		}
+
+		if !self.channels.available_at(channel, now) {
+			return self.channels.check_at(channel, now);
+		}
+
+		self.users.check_at(user, now);
+		self.channels.check_at(channel, now)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn limit(messages: u32) -> Limit {
+		Limit {
+			messages,
+			seconds: 10,
+		}
+	}
+
+	#[test]
+	fn a_limited_channel_does_not_spend_the_users_token() {
+		let mut limits = RateLimits::new(&RateLimit {
+			user: limit(2),
+			channel: limit(1),
+			peer: limit(0),
+		});
+
+		assert_eq!(
+			limits.check_sent(UserId(1), ChannelId(10)),
+			RateLimitResult::Allowed
+		);
+		assert_eq!(
+			limits.check_sent(UserId(1), ChannelId(10)),
+			RateLimitResult::Limited { first: true }
+		);
+		assert_eq!(
+			limits.check_sent(UserId(1), ChannelId(11)),
+			RateLimitResult::Allowed
+		);
+		assert_eq!(
+			limits.check_sent(UserId(1), ChannelId(12)),
+			RateLimitResult::Limited { first: true }
+		);
+	}
+
+	#[test]
+	fn only_the_first_limited_message_is_reported() {
+		let mut limiter = RateLimiter::new(limit(1));
+		let now = Instant::now();
+
+		assert_eq!(limiter.check_at(1, now), RateLimitResult::Allowed);
+		assert_eq!(
+			limiter.check_at(1, now),
+			RateLimitResult::Limited { first: true }
+		);
+		assert_eq!(
+			limiter.check_at(1, now),
+			RateLimitResult::Limited { first: false }
+		);
+		assert!(!limiter.available_at(1, now));
+		assert!(limiter.available_at(1, now + std::time::Duration::from_secs(10)));
+	}
+
+	#[test]
+	fn disabled_limits_always_allow() {
+		let mut limiter = RateLimiter::new(limit(0));
+
+		for _ in 0..100 {
+			assert_eq!(
+				limiter.check(PeerKey::Guild(GuildId(1))),
+				RateLimitResult::Allowed
+			);
+		}
+	}
+}
@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+use serenity::builder::CreateApplicationCommand;
+use serenity::http::{GuildPagination, Http};
+use serenity::model::application::command::Command;
+use serenity::model::prelude::GuildId;
+
+use super::commands;
+use crate::intergalactic_chat::config::CommandScope;
+
+/// The most guilds Discord returns in one page.
+const GUILD_PAGE_SIZE: u64 = 200;
+
+/// The definition of every command, as sent to Discord.
+pub fn definitions() -> Vec<Value> {
+	let registers: [fn(&mut CreateApplicationCommand) -> &mut CreateApplicationCommand; 10] = [
+		commands::ping::register,
+		commands::about::register,
+		commands::network_ban::register,
+		commands::network_unban::register,
+		commands::network_bans::register,
+		commands::link_mute::register,
+		commands::link_unmute::register,
+		commands::link_permissions::register,
+		commands::link_status::register,
+		commands::link_peers::register,
+	];
+
+	registers
+		.iter()
+		.map(|register| {
+			let mut command = CreateApplicationCommand::default();
+			register(&mut command);
+
+			serde_json::to_value(command.0).unwrap()
+		})
+		.collect()
+}
+
+/// Registers the commands globally or in each guild with a linked channel,
+/// depending on `scope`, and removes them everywhere else. Commands that
+/// already match their definitions aren't registered again.
+pub async fn sync_commands(
+	http: &Http, scope: CommandScope, linked_guilds: &HashSet<GuildId>,
+) -> Result<(), String> {
+	let (global, guild) = match scope {
+		CommandScope::Global => (definitions(), Vec::new()),
+		CommandScope::Guild => (Vec::new(), definitions()),
+	};
+	let mut errors = Vec::new();
+
+	if let Err(e) = overwrite_if_changed(http, None, &global).await {
+		errors.push(format!("Unable to register the global commands: {e}"));
+	}
+
+	for id in guilds(http).await.map_err(|e| e.to_string())? {
+		let wanted: &[Value] = if linked_guilds.contains(&id) {
+			&guild
+		} else {
+			&[]
+		};
+
+		if let Err(e) = overwrite_if_changed(http, Some(id), wanted).await {
+			errors.push(format!("Unable to register the commands in {id}: {e}"));
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(errors.join("\n"))
+	}
+}
+
+/// Replaces the commands of `guild`, or the global commands if `None`, with
+/// `definitions` unless they already match.
+async fn overwrite_if_changed(
+	http: &Http, guild: Option<GuildId>, definitions: &[Value],
+) -> Result<(), serenity::Error> {
+	let existing = match guild {
+		Some(g) => http.get_guild_application_commands(g.0).await?,
+		None => http.get_global_application_commands().await?,
+	};
+
+	if up_to_date(&existing, definitions) {
+		return Ok(());
+	}
+
+	let map = Value::from(definitions.to_vec());
+	let scope = match guild {
+		Some(g) => {
+			http.create_guild_application_commands(g.0, &map).await?;
+			g.to_string()
+		}
+		None => {
+			http.create_global_application_commands(&map).await?;
+			"every server".to_owned()
+		}
+	};
+
+	if definitions.is_empty() {
+		println!("Removed the commands from {scope}");
+	} else {
+		println!("Registered {} commands in {scope}", definitions.len());
+	}
+
+	Ok(())
+}
+
+/// Whether the registered commands are the same as `definitions`, ignoring
+/// order and the fields Discord fills in.
+pub fn up_to_date(existing: &[Command], definitions: &[Value]) -> bool {
+	let sorted = |commands: Vec<Value>| {
+		let mut shapes: Vec<Value> = commands.iter().map(shape).collect();
+		shapes.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+		shapes
+	};
+	let existing = existing
+		.iter()
+		.filter_map(|c| serde_json::to_value(c).ok())
+		.collect();
+
+	sorted(existing) == sorted(definitions.to_vec())
+}
+
+/// The parts of a command or option that are set by its definition.
+fn shape(command: &Value) -> Value {
+	let list = |key: &str| {
+		command
+			.get(key)
+			.and_then(Value::as_array)
+			.cloned()
+			.unwrap_or_default()
+	};
+
+	json!({
+		// Commands are chat input commands unless set otherwise.
+		"type": command.get("type").cloned().unwrap_or(json!(1)),
+		"name": command["name"],
+		"description": command["description"],
+		"required": command.get("required").and_then(Value::as_bool).unwrap_or(false),
+		"default_member_permissions": command.get("default_member_permissions").cloned().unwrap_or(Value::Null),
+		// Commands can be used in direct messages unless set otherwise.
+		"dm_permission": command.get("dm_permission").and_then(Value::as_bool).unwrap_or(true),
+		"choices": list("choices")
+			.iter()
+			.map(|c| json!({ "name": c["name"], "value": c["value"] }))
+			.collect::<Vec<_>>(),
+		"options": list("options").iter().map(shape).collect::<Vec<_>>(),
+	})
+}
+
+/// Every guild the bot is in.
+async fn guilds(http: &Http) -> Result<Vec<GuildId>, serenity::Error> {
+	let mut guilds = Vec::new();
+
+	loop {
+		let after = guilds.last().map(|g| GuildPagination::After(*g));
+		let page = http
+			.get_guilds(after.as_ref(), Some(GUILD_PAGE_SIZE))
+			.await?;
+		let full = page.len() as u64 == GUILD_PAGE_SIZE;
+
+		guilds.extend(page.into_iter().map(|g| g.id));
+
+		if !full {
+			return Ok(guilds);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// How Discord returns `definition` once it's registered.
+	fn registered(definition: &Value) -> Command {
+		let mut command = definition.to_owned();
+		let fields = json!({
+			"id": "1",
+			"type": 1,
+			"application_id": "2",
+			"version": "3",
+			"dm_permission": definition.get("dm_permission").cloned().unwrap_or(json!(true)),
+			"default_member_permissions": definition.get("default_member_permissions"),
+		});
+
+		command
+			.as_object_mut()
+			.unwrap()
+			.extend(fields.as_object().unwrap().to_owned());
+
+		serde_json::from_value(command).unwrap()
+	}
+
+	#[test]
+	fn registered_definitions_are_up_to_date() {
+		let definitions = definitions();
+		let mut existing: Vec<Command> = definitions.iter().map(registered).collect();
+		existing.reverse();
+
+		assert!(up_to_date(&existing, &definitions));
+		assert!(up_to_date(&[], &[]));
+	}
+
+	#[test]
+	fn server_only_commands_cant_be_used_in_direct_messages() {
+		for command in definitions() {
+			let server_only = [
+				"network-ban",
+				"network-unban",
+				"network-bans",
+				"link-mute",
+				"link-unmute",
+			]
+			.contains(&command["name"].as_str().unwrap());
+
+			assert_eq!(
+				command["dm_permission"] == json!(false),
+				server_only,
+				"{}",
+				command["name"]
+			);
+		}
+	}
+
+	#[test]
+	fn changed_definitions_are_not_up_to_date() {
+		let definitions = definitions();
+		let existing: Vec<Command> = definitions.iter().map(registered).collect();
+
+		let mut changed = definitions.to_owned();
+		changed[0]["description"] = json!("Something else.");
+		assert!(!up_to_date(&existing, &changed));
+
+		let mut changed = definitions.to_owned();
+		let ban = changed
+			.iter_mut()
+			.find(|c| c["name"] == "network-ban")
+			.unwrap();
+		ban["dm_permission"] = json!(true);
+		assert!(!up_to_date(&existing, &changed));
+
+		assert!(!up_to_date(&existing, &definitions[1..]));
+		assert!(!up_to_date(&existing[1..], &definitions));
+		assert!(!up_to_date(&existing, &[]));
+	}
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serenity::http::Http;
+use serenity::model::channel::Message;
+use serenity::model::id::ChannelId;
+use serenity::model::webhook::Webhook;
+use serenity::prelude::Mutex;
+use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::Semaphore;
+use tokio::task;
+use tokio::time::sleep;
+
+use super::bot::DiscordHandler;
+use super::cache::{CacheValue, MessageCache};
+use super::mod_log::ModLog;
+use super::util::execute_message_for_webhook;
+use super::webhooks::{WebhookFailure, WebhookHealth};
+use crate::intergalactic_chat::metrics::LinkMetrics;
+
+/// How many times a message is sent if Discord rate limits it.
+const SEND_ATTEMPTS: u32 = 3;
+
+/// How many messages can wait to be posted in each channel.
+const QUEUE_CAPACITY: usize = 100;
+
+/// A message to post with a webhook.
+struct Job {
+	message: Message,
+	webhook: Webhook,
+}
+
+/// Everything a worker needs to post messages and record the result.
+#[derive(Clone)]
+struct Delivery {
+	http: Arc<Http>,
+	permits: Arc<Semaphore>,
+	message_cache: Arc<Mutex<MessageCache>>,
+	metrics: Arc<LinkMetrics>,
+	webhooks: Arc<Mutex<Vec<Webhook>>>,
+	webhook_health: Arc<WebhookHealth>,
+	mod_log: Arc<Mutex<ModLog>>,
+}
+
+/// Posts mirrored messages with one worker per linked channel, so messages
+/// appear in each channel in the order they were received. At most
+/// `max_concurrent_sends` messages are sent at once across every channel.
+pub struct SendQueue {
+	delivery: Delivery,
+	workers: HashMap<ChannelId, Sender<Job>>,
+}
+
+impl SendQueue {
+	pub fn new(handler: &DiscordHandler, http: Arc<Http>, max_concurrent_sends: usize) -> Self {
+		Self {
+			delivery: Delivery {
+				http,
+				permits: Arc::new(Semaphore::new(max_concurrent_sends)),
+				message_cache: Arc::clone(&handler.message_cache),
+				metrics: Arc::clone(&handler.metrics),
+				webhooks: Arc::clone(&handler.webhooks),
+				webhook_health: Arc::clone(&handler.webhook_health),
+				mod_log: Arc::clone(&handler.mod_log),
+			},
+			workers: HashMap::new(),
+		}
+	}
+
+	/// Queues `message` to be posted with `webhook`, after every message
+	/// already queued for the webhook's channel. Waits if the channel already
+	/// has [`QUEUE_CAPACITY`] messages queued, which slows down receiving from
+	/// the broker.
+	pub async fn push(&mut self, message: Message, webhook: Webhook) {
+		let channel = match webhook.channel_id {
+			Some(c) => c,
+			None => return,
+		};
+		let mut job = Job { message, webhook };
+
+		if let Some(sender) = self.workers.get(&channel) {
+			job = match sender.send(job).await {
+				Ok(()) => return,
+				// The worker stopped, so start a new one.
+				Err(e) => e.0,
+			};
+		}
+
+		let (sender, mut receiver) = mpsc::channel::<Job>(QUEUE_CAPACITY);
+		let delivery = self.delivery.to_owned();
+
+		task::spawn(async move {
+			while let Some(job) = receiver.recv().await {
+				delivery.send(job).await;
+			}
+		});
+
+		sender.send(job).await.ok();
+		self.workers.insert(channel, sender);
+	}
+}
+
+impl Delivery {
+	/// Posts the message of `job`, retrying if Discord rate limits it.
+	async fn send(&self, job: Job) {
+		let Job { message, webhook } = job;
+
+		for attempt in 1..=SEND_ATTEMPTS {
+			let permit = match self.permits.acquire().await {
+				Ok(p) => p,
+				Err(_) => return,
+			};
+			let start = Instant::now();
+			let m = execute_message_for_webhook(message.to_owned(), &self.http, &webhook).await;
+
+			if !matches!(m, Ok(None)) {
+				self.metrics.observe_webhook(start.elapsed(), m.is_err());
+			}
+
+			match m {
+				Ok(Some(m)) => {
+					self.webhook_health
+						.recover(&self.http, &self.mod_log, m.channel_id)
+						.await;
+					self.message_cache.lock().await.push_into_value(
+						message.id,
+						CacheValue {
+							related_channel_id: m.channel_id,
+							related_message_id: m.id,
+							related_webhook_id: webhook.id,
+						},
+					);
+				}
+				Ok(None) => (),
+				Err(e)
+					if attempt < SEND_ATTEMPTS
+						&& WebhookFailure::classify(&e) == WebhookFailure::RateLimited =>
+				{
+					// Later messages for this channel wait too, keeping them in order.
+					// Other channels can use the permit in the meantime.
+					drop(permit);
+					sleep(Duration::from_secs(2u64.pow(attempt))).await;
+					continue;
+				}
+				Err(e) => {
+					println!("Error sending message {e}");
+
+					if let Some(channel) = webhook.channel_id {
+						self.webhook_health
+							.report(
+								&self.http,
+								&self.webhooks,
+								&self.mod_log,
+								channel,
+								webhook.id,
+								&e,
+							)
+							.await;
+					}
+				}
+			}
+
+			return;
+		}
+	}
+}
@@ -0,0 +1,55 @@
+use std::{
+	collections::HashMap,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+use serenity::{
+	client::bridge::gateway::ShardManager,
+	model::prelude::GuildId,
+	prelude::{Mutex, TypeMapKey},
+};
+
+/// How long a peer is listed by `/link-status` after its last message.
+pub const RECENT_PEER_WINDOW: Duration = Duration::from_secs(60 * 30);
+
+/// Gives commands access to the shard manager, to report gateway latency.
+pub struct ShardManagerContainer;
+
+impl TypeMapKey for ShardManagerContainer {
+	type Value = Arc<Mutex<ShardManager>>;
+}
+
+/// Information about the link shown by `/link-status`.
+#[derive(Debug, Clone)]
+pub struct LinkStatus {
+	pub started: Instant,
+	/// When a message from each origin guild was last received.
+	pub peers: HashMap<GuildId, Instant>,
+}
+
+impl LinkStatus {
+	pub fn new() -> Self {
+		Self {
+			started: Instant::now(),
+			peers: HashMap::new(),
+		}
+	}
+
+	pub fn saw_peer(&mut self, guild: GuildId) {
+		self.peers.insert(guild, Instant::now());
+	}
+
+	/// Peers seen within [`RECENT_PEER_WINDOW`], most recent first.
+	pub fn recent_peers(&self) -> Vec<(GuildId, Duration)> {
+		let mut peers: Vec<_> = self
+			.peers
+			.iter()
+			.map(|(g, t)| (*g, t.elapsed()))
+			.filter(|(_, e)| *e <= RECENT_PEER_WINDOW)
+			.collect();
+		peers.sort_by_key(|(_, e)| *e);
+
+		peers
+	}
+}
@@ -2,40 +2,66 @@ use std::ops::Deref;
 
 use serenity::{
 	builder::ParseValue,
+	http::Http,
 	model::{
 		prelude::{AttachmentType, ChannelId, Embed, Message},
 		webhook::Webhook,
 	},
-	prelude::Context,
 };
 
+use crate::intergalactic_chat::mqtt::presence::LinkedChannel;
+
 /// Get the webhook for the linked channel, if the channel doesn't already
 /// have one create a new one.
-///
-/// ## Panics
-///
-/// - Panics if the webhook cannot be created, because the bot won't work.
-/// - Panics if the list of webhooks can't be returned, because the bot
-/// won't work.
 pub async fn get_link_webhook(
-	channel: ChannelId, webhook_name: &str, context: &Context,
-) -> Webhook {
-	match channel.webhooks(&context).await {
-		Ok(w) => match w.iter().find(|i| i.name == Some(webhook_name.to_owned())) {
-			Some(w) => w.to_owned(),
-			None => match channel.create_webhook(&context, webhook_name).await {
-				Ok(w) => w,
-				Err(e) => panic!("Error creating webhook: {e}"),
+	channel: ChannelId, webhook_name: &str, http: &Http,
+) -> Result<Webhook, serenity::Error> {
+	let webhooks = channel.webhooks(http).await?;
+
+	match webhooks
+		.into_iter()
+		.find(|i| i.name == Some(webhook_name.to_owned()))
+	{
+		Some(w) => Ok(w),
+		None => channel.create_webhook(http, webhook_name).await,
+	}
+}
+
+/// Looks up the names of the linked channels and their guilds, for the
+/// heartbeat. Channels that can't be fetched are skipped.
+pub async fn linked_channels(channels: &[u64], http: &Http) -> Vec<LinkedChannel> {
+	let mut linked = Vec::new();
+
+	for channel in channels {
+		let channel = match ChannelId(*channel).to_channel(http).await {
+			Ok(c) => match c.guild() {
+				Some(c) => c,
+				None => continue,
 			},
-		},
-		Err(e) => panic!("Error getting webhooks: {e}"),
+			Err(e) => {
+				println!("Error getting channel {channel}: {e}");
+				continue;
+			}
+		};
+		let guild = match channel.guild_id.to_partial_guild(http).await {
+			Ok(g) => g.name,
+			Err(_) => channel.guild_id.to_string(),
+		};
+
+		linked.push(LinkedChannel {
+			guild,
+			channel: channel.name,
+			guild_id: Some(channel.guild_id),
+		});
 	}
+
+	linked
 }
 
 /// Builds a reply embed using the type provided by [`serenity::model::Message::referenced_message`].
 ///
 /// Should only be used for webhooks.
-pub fn build_reply_for_webhook(rm: &Box<Message>) -> serde_json::Value {
+pub fn build_reply_for_webhook(rm: &Message) -> serde_json::Value {
 	Embed::fake(|e| {
 		e.description(format!(
 			"**[Reply to:]({})** {}{}",
@@ -60,13 +86,13 @@ pub fn build_reply_for_webhook(rm: &Box<Message>) -> serde_json::Value {
 }
 
 pub async fn execute_message_for_webhook(
-	message: Message, context: &Context, webhook: &Webhook,
+	message: Message, http: &Http, webhook: &Webhook,
 ) -> Result<Option<Message>, serenity::Error> {
 	if message.channel_id.as_u64() == webhook.channel_id.unwrap().as_u64() {
 		return Ok(None);
 	}
 
-	let x = webhook.execute(&context, true, |wh| {
+	let x = webhook.execute(http, true, |wh| {
 		wh.content(message.content);
 		wh.avatar_url(message.author.face());
 		wh.username(message.author.name);
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::http::{Http, HttpError};
+use serenity::model::prelude::{ChannelId, WebhookId};
+use serenity::model::webhook::Webhook;
+use serenity::prelude::{Mutex, RwLock};
+use tokio::sync::Notify;
+use tokio::time::timeout;
+
+use super::mod_log::{ModLog, ModLogEvent};
+use super::util::get_link_webhook;
+use crate::intergalactic_chat::config::Config;
+
+/// How often every webhook is checked, in case it was deleted or the bot's
+/// permissions were changed.
+pub const WEBHOOK_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 5);
+
+/// Why a webhook couldn't be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookFailure {
+	/// The webhook was deleted, it's recreated automatically.
+	UnknownWebhook,
+	/// The message being edited or deleted no longer exists.
+	UnknownMessage,
+	/// The bot can't manage webhooks or post in the channel.
+	MissingPermissions,
+	RateLimited,
+	Other(String),
+}
+
+impl WebhookFailure {
+	pub fn classify(error: &serenity::Error) -> Self {
+		match error {
+			serenity::Error::Http(e) => match e.as_ref() {
+				HttpError::UnsuccessfulRequest(r) => match (r.status_code.as_u16(), r.error.code) {
+					(_, 10008) => Self::UnknownMessage,
+					(_, 10015) => Self::UnknownWebhook,
+					// Missing Access and Missing Permissions.
+					(_, 50001 | 50013) => Self::MissingPermissions,
+					(429, _) => Self::RateLimited,
+					_ => Self::Other(r.error.message.to_owned()),
+				},
+				e => Self::Other(e.to_string()),
+			},
+			e => Self::Other(e.to_string()),
+		}
+	}
+}
+
+impl fmt::Display for WebhookFailure {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::UnknownWebhook => write!(f, "The webhook was deleted"),
+			Self::UnknownMessage => write!(f, "The message was deleted"),
+			Self::MissingPermissions => write!(
+				f,
+				"The bot is missing the Manage Webhooks or Send Messages permission"
+			),
+			Self::RateLimited => write!(f, "Rate limited by Discord"),
+			Self::Other(e) => write!(f, "{e}"),
+		}
+	}
+}
+
+/// Tracks the linked channels whose webhook is failing. Messages are still
+/// sent to degraded channels, in case the problem has been fixed.
+#[derive(Debug, Default)]
+pub struct WebhookHealth {
+	degraded: Mutex<HashMap<ChannelId, WebhookFailure>>,
+	/// Notified to check the webhooks now, rather than waiting for
+	/// [`WEBHOOK_CHECK_INTERVAL`].
+	check: Notify,
+}
+
+impl WebhookHealth {
+	/// The degraded channels, and why.
+	pub async fn degraded(&self) -> HashMap<ChannelId, WebhookFailure> {
+		self.degraded.lock().await.to_owned()
+	}
+
+	/// Asks [`maintain`] to check the webhooks now.
+	pub fn check_now(&self) {
+		self.check.notify_one();
+	}
+
+	/// Marks `channel` as degraded, posting a notice to its server's mod-log
+	/// the first time.
+	pub async fn degrade(
+		&self, http: &Http, mod_log: &Mutex<ModLog>, channel: ChannelId, failure: WebhookFailure,
+	) {
+		if self
+			.degraded
+			.lock()
+			.await
+			.insert(channel, failure.to_owned())
+			.is_some()
+		{
+			return;
+		}
+
+		println!("The webhook in {channel} is degraded: {failure}");
+
+		let mod_log = mod_log.lock().await;
+
+		mod_log
+			.post(
+				http,
+				mod_log.guild_of(channel),
+				ModLogEvent::WebhookDegraded {
+					channel,
+					reason: failure.to_string(),
+				},
+			)
+			.await;
+	}
+
+	/// Marks `channel` as healthy, posting a notice to its server's mod-log if
+	/// it was degraded.
+	pub async fn recover(&self, http: &Http, mod_log: &Mutex<ModLog>, channel: ChannelId) {
+		if self.degraded.lock().await.remove(&channel).is_none() {
+			return;
+		}
+
+		println!("The webhook in {channel} has recovered");
+
+		let mod_log = mod_log.lock().await;
+
+		mod_log
+			.post(
+				http,
+				mod_log.guild_of(channel),
+				ModLogEvent::WebhookRecovered { channel },
+			)
+			.await;
+	}
+
+	/// Handles a failure to send, edit or delete a message posted with `webhook`
+	/// in `channel`. Deleted webhooks are forgotten and recreated straight away.
+	pub async fn report(
+		&self, http: &Http, webhooks: &Mutex<Vec<Webhook>>, mod_log: &Mutex<ModLog>,
+		channel: ChannelId, webhook: WebhookId, error: &serenity::Error,
+	) {
+		match WebhookFailure::classify(error) {
+			WebhookFailure::UnknownWebhook => {
+				let mut webhooks = webhooks.lock().await;
+
+				// Mirrors posted before the webhook was recreated still have its
+				// old ID.
+				if webhooks.iter().any(|w| w.id == webhook) {
+					println!("The webhook in {channel} was deleted, recreating it");
+
+					webhooks.retain(|w| w.id != webhook);
+					self.check_now();
+				}
+			}
+			WebhookFailure::UnknownMessage => (),
+			WebhookFailure::RateLimited => println!("Rate limited by Discord in {channel}"),
+			failure => self.degrade(http, mod_log, channel, failure).await,
+		}
+	}
+}
+
+/// Forgets the webhooks of unlinked channels and creates webhooks named
+/// `name` for linked channels without one. Channels whose webhook can't be
+/// created are degraded, and returned along with the error.
+pub async fn ensure_webhooks(
+	http: &Http, webhooks: &Mutex<Vec<Webhook>>, health: &WebhookHealth, mod_log: &Mutex<ModLog>,
+	channels: &[u64], name: &str,
+) -> Vec<(ChannelId, serenity::Error)> {
+	let mut current = webhooks.lock().await.to_owned();
+	let mut failures = Vec::new();
+
+	current.retain(|w| w.channel_id.is_some_and(|c| channels.contains(c.as_u64())));
+	health
+		.degraded
+		.lock()
+		.await
+		.retain(|c, _| channels.contains(c.as_u64()));
+
+	for channel in channels {
+		let channel = ChannelId::from(*channel);
+
+		if current.iter().any(|w| w.channel_id == Some(channel)) {
+			continue;
+		}
+
+		match get_link_webhook(channel, name, http).await {
+			Ok(w) => {
+				current.push(w);
+				health.recover(http, mod_log, channel).await;
+			}
+			Err(e) => {
+				health
+					.degrade(http, mod_log, channel, WebhookFailure::classify(&e))
+					.await;
+				failures.push((channel, e));
+			}
+		}
+	}
+
+	*webhooks.lock().await = current;
+
+	failures
+}
+
+/// Checks every webhook still exists and can be used, every
+/// [`WEBHOOK_CHECK_INTERVAL`] or when [`WebhookHealth::check_now`] is called.
+/// Deleted webhooks are recreated.
+pub async fn maintain(
+	http: Arc<Http>, config: Arc<RwLock<Config>>, webhooks: Arc<Mutex<Vec<Webhook>>>,
+	health: Arc<WebhookHealth>, mod_log: Arc<Mutex<ModLog>>, name: String,
+) {
+	loop {
+		timeout(WEBHOOK_CHECK_INTERVAL, health.check.notified())
+			.await
+			.ok();
+
+		let current = webhooks.lock().await.to_owned();
+
+		for webhook in current {
+			let channel = match webhook.channel_id {
+				Some(c) => c,
+				None => continue,
+			};
+
+			// Degraded channels recover when a message is sent successfully, as
+			// the bot may be able to see the webhook but not post with it.
+			match http.get_webhook(webhook.id.0).await {
+				Ok(_) => (),
+				Err(e) => match WebhookFailure::classify(&e) {
+					WebhookFailure::UnknownWebhook => {
+						webhooks.lock().await.retain(|w| w.id != webhook.id);
+					}
+					WebhookFailure::RateLimited => (),
+					failure => health.degrade(&http, &mod_log, channel, failure).await,
+				},
+			}
+		}
+
+		let channels = config.read().await.discord.channels.to_owned();
+
+		ensure_webhooks(&http, &webhooks, &health, &mod_log, &channels, &name).await;
+	}
+}
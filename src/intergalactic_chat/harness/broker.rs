@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use rumqttc::mqttbytes::{self, matches};
+use rumqttc::{
+	ConnAck, ConnectReturnCode, Packet, PingResp, PubAck, PubComp, PubRec, Publish, QoS, SubAck,
+	SubscribeReasonCode,
+};
+use serenity::prelude::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task;
+
+/// The largest packet the broker accepts.
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// The topic filters each connected client subscribed to, and where to send
+/// the packets for it.
+type Subscriptions = Arc<Mutex<Vec<(String, UnboundedSender<Packet>)>>>;
+
+/// A minimal MQTT 3.1.1 broker on a local port. Publishes are forwarded to
+/// every matching subscription at most once, retained messages and wills are
+/// ignored.
+pub struct Broker {
+	pub port: u16,
+}
+
+impl Broker {
+	pub async fn start() -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let port = listener.local_addr().unwrap().port();
+		let subscriptions = Subscriptions::default();
+
+		task::spawn(async move {
+			while let Ok((stream, _)) = listener.accept().await {
+				task::spawn(serve(stream, Arc::clone(&subscriptions)));
+			}
+		});
+
+		Self { port }
+	}
+}
+
+async fn serve(stream: TcpStream, subscriptions: Subscriptions) {
+	let (mut reader, mut writer) = stream.into_split();
+	let (outgoing, mut packets) = unbounded_channel::<Packet>();
+
+	task::spawn(async move {
+		while let Some(packet) = packets.recv().await {
+			let mut buffer = BytesMut::new();
+
+			if write(&packet, &mut buffer).is_err() || writer.write_all(&buffer).await.is_err() {
+				return;
+			}
+		}
+	});
+
+	let mut buffer = BytesMut::new();
+
+	loop {
+		match reader.read_buf(&mut buffer).await {
+			Ok(0) | Err(_) => return,
+			Ok(_) => (),
+		}
+
+		loop {
+			let packet = match mqttbytes::v4::read(&mut buffer, MAX_PACKET_SIZE) {
+				Ok(p) => p,
+				Err(mqttbytes::Error::InsufficientBytes(_)) => break,
+				Err(_) => return,
+			};
+
+			let reply = match packet {
+				Packet::Connect(_) => {
+					Packet::ConnAck(ConnAck::new(ConnectReturnCode::Success, false))
+				}
+				Packet::Subscribe(s) => {
+					let mut subscriptions = subscriptions.lock().await;
+
+					for filter in &s.filters {
+						subscriptions.push((filter.path.to_owned(), outgoing.clone()));
+					}
+
+					Packet::SubAck(SubAck::new(
+						s.pkid,
+						s.filters
+							.iter()
+							.map(|f| SubscribeReasonCode::Success(f.qos))
+							.collect(),
+					))
+				}
+				Packet::Publish(p) => {
+					for (filter, subscriber) in subscriptions.lock().await.iter() {
+						if matches(&p.topic, filter) {
+							let forwarded =
+								Publish::new(&p.topic, QoS::AtMostOnce, p.payload.to_vec());
+							subscriber.send(Packet::Publish(forwarded)).ok();
+						}
+					}
+
+					match p.qos {
+						QoS::AtMostOnce => continue,
+						QoS::AtLeastOnce => Packet::PubAck(PubAck::new(p.pkid)),
+						QoS::ExactlyOnce => Packet::PubRec(PubRec::new(p.pkid)),
+					}
+				}
+				Packet::PubRel(r) => Packet::PubComp(PubComp::new(r.pkid)),
+				Packet::PingReq => Packet::PingResp,
+				Packet::Disconnect => return,
+				_ => continue,
+			};
+
+			outgoing.send(reply).ok();
+		}
+	}
+}
+
+fn write(packet: &Packet, buffer: &mut BytesMut) -> Result<usize, mqttbytes::Error> {
+	match packet {
+		Packet::ConnAck(p) => p.write(buffer),
+		Packet::SubAck(p) => p.write(buffer),
+		Packet::Publish(p) => p.write(buffer),
+		Packet::PubAck(p) => p.write(buffer),
+		Packet::PubRec(p) => p.write(buffer),
+		Packet::PubComp(p) => p.write(buffer),
+		Packet::PingResp => PingResp.write(buffer),
+		_ => Ok(0),
+	}
+}
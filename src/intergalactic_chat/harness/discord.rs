@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+use serenity::prelude::Mutex;
+use tokio::task;
+
+/// A request made to [`FakeDiscord`], and the response it was given.
+#[derive(Debug, Clone)]
+pub struct Recorded {
+	pub method: Method,
+	/// The path without the API version, such as `channels/1/messages`.
+	pub path: String,
+	/// The JSON body, `null` if there wasn't one.
+	pub body: Value,
+	pub response: Value,
+}
+
+impl Recorded {
+	/// Whether this request posted a message with a webhook.
+	pub fn is_execution(&self) -> bool {
+		self.method == Method::POST
+			&& self.path.starts_with("webhooks/")
+			&& self.path.split('/').count() == 3
+	}
+}
+
+#[derive(Default)]
+struct State {
+	/// The guild of each channel.
+	channels: HashMap<u64, u64>,
+	webhooks: Vec<Value>,
+	next_id: u64,
+	requests: Vec<Recorded>,
+}
+
+impl State {
+	fn next_id(&mut self) -> u64 {
+		self.next_id += 1;
+		self.next_id
+	}
+
+	fn webhook(&self, id: &str) -> Option<Value> {
+		self.webhooks.iter().find(|w| w["id"] == id).cloned()
+	}
+}
+
+/// A local stand-in for the parts of the Discord REST API the bot uses. Every
+/// request is recorded, so tests can check what the bot did.
+pub struct FakeDiscord {
+	/// Where to send requests instead of `https://discord.com/`.
+	pub url: String,
+	state: Arc<Mutex<State>>,
+}
+
+impl FakeDiscord {
+	/// Starts the server, with `channels` as pairs of channel and guild IDs.
+	pub async fn start(channels: &[(u64, u64)]) -> Self {
+		let state = Arc::new(Mutex::new(State {
+			channels: channels.iter().copied().collect(),
+			next_id: 5_000_000_000_000_000_000,
+			..Default::default()
+		}));
+		let served = Arc::clone(&state);
+		let make_service = make_service_fn(move |_| {
+			let state = Arc::clone(&served);
+
+			async move {
+				Ok::<_, Infallible>(service_fn(move |request| {
+					let state = Arc::clone(&state);
+
+					async move { Ok::<_, Infallible>(handle(request, &state).await) }
+				}))
+			}
+		});
+		let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+		let url = format!("http://{}/", server.local_addr());
+
+		task::spawn(server);
+
+		Self { url, state }
+	}
+
+	/// Deletes the webhooks in `channel`, as a server's admins might.
+	pub async fn delete_webhooks(&self, channel: u64) {
+		let channel = channel.to_string();
+
+		self.state
+			.lock()
+			.await
+			.webhooks
+			.retain(|w| w["channel_id"] != *channel);
+	}
+
+	/// Every request made so far, oldest first.
+	pub async fn requests(&self) -> Vec<Recorded> {
+		self.state.lock().await.requests.to_owned()
+	}
+
+	/// Waits for a request matching `predicate`, panicking with `description`
+	/// if there isn't one within five seconds.
+	pub async fn wait_for(
+		&self, description: &str, predicate: impl Fn(&Recorded) -> bool,
+	) -> Recorded {
+		super::eventually(description, || async {
+			self.requests().await.into_iter().find(|r| predicate(r))
+		})
+		.await
+	}
+}
+
+async fn handle(request: Request<Body>, state: &Mutex<State>) -> Response<Body> {
+	let method = request.method().to_owned();
+	let path = request
+		.uri()
+		.path()
+		.trim_start_matches("/api/v10/")
+		.to_owned();
+	let paginated = request.uri().query().is_some_and(|q| q.contains("after="));
+	let body = hyper::body::to_bytes(request.into_body())
+		.await
+		.unwrap_or_default();
+	let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+	let segments: Vec<&str> = path.split('/').collect();
+
+	// The error code Discord gives if what the request is for doesn't exist.
+	let unknown = match segments.as_slice() {
+		["webhooks", ..] => (10015, "Unknown Webhook"),
+		["channels", _, "messages", ..] => (10008, "Unknown Message"),
+		_ => (0, "Unknown"),
+	};
+
+	let mut state = state.lock().await;
+	let response = match (&method, segments.as_slice()) {
+		(&Method::GET, ["channels", id]) => {
+			let id = id.parse().unwrap_or_default();
+
+			state
+				.channels
+				.get(&id)
+				.map(|guild| guild_channel(id, *guild))
+		}
+		(&Method::GET, ["channels", id, "webhooks"]) => Some(Value::from(
+			state
+				.webhooks
+				.iter()
+				.filter(|w| w["channel_id"] == *id)
+				.cloned()
+				.collect::<Vec<_>>(),
+		)),
+		(&Method::POST, ["channels", id, "webhooks"]) => {
+			let webhook_id = state.next_id();
+			let guild = state.channels.get(&id.parse().unwrap_or_default()).copied();
+			let webhook = json!({
+				"id": webhook_id.to_string(),
+				"type": 1,
+				"channel_id": id,
+				"guild_id": guild.map(|g| g.to_string()),
+				"name": body["name"],
+				"avatar": null,
+				"token": format!("token-{webhook_id}"),
+			});
+
+			state.webhooks.push(webhook.to_owned());
+
+			Some(webhook)
+		}
+		(&Method::GET, ["webhooks", id]) => state.webhook(id),
+		(&Method::POST, ["webhooks", id, _]) => match state.webhook(id) {
+			Some(webhook) => {
+				let author = json!({
+					"id": webhook["id"],
+					"username": body["username"],
+					"discriminator": "0000",
+					"avatar": null,
+					"bot": true,
+				});
+				let mut message = message(
+					state.next_id(),
+					webhook["channel_id"].as_str().unwrap().parse().unwrap(),
+					author,
+					body["content"].as_str().unwrap_or_default(),
+				);
+				message["webhook_id"] = webhook["id"].to_owned();
+
+				Some(message)
+			}
+			None => None,
+		},
+		(&Method::PATCH, ["webhooks", id, _, "messages", message_id]) => {
+			state.webhook(id).map(|webhook| {
+				message(
+					message_id.parse().unwrap(),
+					webhook["channel_id"].as_str().unwrap().parse().unwrap(),
+					user(id.parse().unwrap(), "Webhook", true),
+					body["content"].as_str().unwrap_or_default(),
+				)
+			})
+		}
+		(&Method::DELETE, ["channels", _, "messages", _]) => Some(Value::Null),
+		(&Method::POST, ["users", "@me", "channels"]) => Some(json!({
+			"id": state.next_id().to_string(),
+			"type": 1,
+			"recipients": [user(
+				body["recipient_id"].as_u64().unwrap_or_default(),
+				"Someone",
+				false,
+			)],
+			"last_message_id": null,
+		})),
+		(&Method::POST, ["channels", id, "messages"]) => Some(message(
+			state.next_id(),
+			id.parse().unwrap_or_default(),
+			user(1, "Link", true),
+			body["content"].as_str().unwrap_or_default(),
+		)),
+		(&Method::GET, ["users", "@me", "guilds"]) if !paginated => {
+			let mut guilds: Vec<u64> = state.channels.values().copied().collect();
+			guilds.sort();
+			guilds.dedup();
+
+			Some(Value::from(
+				guilds
+					.iter()
+					.map(|g| {
+						json!({
+							"id": g.to_string(),
+							"name": format!("Server {g}"),
+							"icon": null,
+							"owner": false,
+							"permissions": "0",
+							"features": [],
+						})
+					})
+					.collect::<Vec<_>>(),
+			))
+		}
+		(&Method::GET, ["users", "@me", "guilds"]) => Some(json!([])),
+		(&Method::GET | &Method::PUT, ["applications", _, "commands"])
+		| (&Method::GET | &Method::PUT, ["applications", _, "guilds", _, "commands"]) => Some(json!([])),
+		_ => None,
+	};
+
+	state.requests.push(Recorded {
+		method,
+		path,
+		body,
+		response: response.to_owned().unwrap_or(Value::Null),
+	});
+
+	match response {
+		Some(Value::Null) => Response::builder()
+			.status(StatusCode::NO_CONTENT)
+			.body(Body::empty()),
+		Some(v) => Response::builder()
+			.header("Content-Type", "application/json")
+			.body(Body::from(v.to_string())),
+		None => Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.header("Content-Type", "application/json")
+			.body(Body::from(
+				json!({ "message": unknown.1, "code": unknown.0 }).to_string(),
+			)),
+	}
+	.unwrap()
+}
+
+/// A Discord user.
+pub fn user(id: u64, name: &str, bot: bool) -> Value {
+	json!({
+		"id": id.to_string(),
+		"username": name,
+		"discriminator": "0001",
+		"avatar": null,
+		"bot": bot,
+	})
+}
+
+/// A message sent by `author` in `channel`.
+pub fn message(id: u64, channel: u64, author: Value, content: &str) -> Value {
+	json!({
+		"id": id.to_string(),
+		"channel_id": channel.to_string(),
+		"author": author,
+		"content": content,
+		"timestamp": "2023-04-01T12:00:00.000000+00:00",
+		"edited_timestamp": null,
+		"tts": false,
+		"mention_everyone": false,
+		"mentions": [],
+		"mention_roles": [],
+		"attachments": [],
+		"embeds": [],
+		"pinned": false,
+		"type": 0,
+	})
+}
+
+fn guild_channel(id: u64, guild: u64) -> Value {
+	json!({
+		"id": id.to_string(),
+		"type": 0,
+		"guild_id": guild.to_string(),
+		"name": format!("linked-{id}"),
+		"position": 0,
+		"permission_overwrites": [],
+		"nsfw": false,
+	})
+}
@@ -0,0 +1,437 @@
+use hyper::Method;
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::Timestamp;
+use tokio::time::sleep;
+
+use super::broker::Broker;
+use super::discord::{message, user};
+use super::*;
+use crate::intergalactic_chat::config::{Limit, RateLimit};
+use crate::intergalactic_chat::discord::bans::BanEntry;
+use crate::intergalactic_chat::discord::filter::{
+	ContentFilter, FilterAction, FilterRule, FilterRules,
+};
+use crate::intergalactic_chat::discord::rate_limit::RateLimits;
+
+const CHANNEL_A: u64 = 1_100_000_000_000_000_001;
+const GUILD_A: u64 = 1_200_000_000_000_000_001;
+const CHANNEL_B: u64 = 1_100_000_000_000_000_002;
+const GUILD_B: u64 = 1_200_000_000_000_000_002;
+const CHANNEL_C: u64 = 1_100_000_000_000_000_003;
+const GUILD_C: u64 = 1_200_000_000_000_000_003;
+const ALICE: u64 = 1_300_000_000_000_000_001;
+const MALLORY: u64 = 1_300_000_000_000_000_002;
+
+/// A message sent by Alice in `CHANNEL_A`.
+fn from_alice(id: u64, content: &str) -> Value {
+	message(id, CHANNEL_A, user(ALICE, "Alice", false), content)
+}
+
+/// Two ready bots on the same broker, linking a channel each.
+async fn two_bots() -> (Broker, Bot, Bot) {
+	let broker = Broker::start().await;
+	let a = Bot::start(&broker, "link-a", &[(CHANNEL_A, GUILD_A)]).await;
+	let b = Bot::start(&broker, "link-b", &[(CHANNEL_B, GUILD_B)]).await;
+
+	a.ready().await;
+	b.ready().await;
+
+	(broker, a, b)
+}
+
+/// A ready bot linking two channels, so messages are mirrored between them by
+/// the same bot.
+async fn one_bot() -> (Broker, Bot) {
+	let broker = Broker::start().await;
+	let bot = Bot::start(
+		&broker,
+		"link-a",
+		&[(CHANNEL_A, GUILD_A), (CHANNEL_B, GUILD_B)],
+	)
+	.await;
+
+	bot.ready().await;
+
+	(broker, bot)
+}
+
+/// The ID of the mirror of a message, posted in response to `execution`.
+fn mirror_id(execution: &discord::Recorded) -> u64 {
+	execution.response["id"].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn ready_creates_a_webhook_in_each_linked_channel() {
+	let (_broker, bot) = one_bot().await;
+
+	for channel in [CHANNEL_A, CHANNEL_B] {
+		bot.discord
+			.wait_for("The webhook was never created", |r| {
+				r.method == Method::POST && r.path == format!("channels/{channel}/webhooks")
+			})
+			.await;
+	}
+
+	assert_eq!(bot.handler.webhooks.lock().await.len(), 2);
+}
+
+#[tokio::test]
+async fn messages_are_relayed_to_other_bots() {
+	let (_broker, a, b) = two_bots().await;
+
+	a.send(from_alice(1_400_000_000_000_000_001, "Hello")).await;
+
+	let execution = b
+		.discord
+		.wait_for("The message was never relayed", |r| r.is_execution())
+		.await;
+
+	assert_eq!(execution.body["content"], "Hello");
+	assert_eq!(execution.body["username"], "Alice");
+
+	// The message is already in the channel it was sent in.
+	assert!(!a.discord.requests().await.iter().any(|r| r.is_execution()));
+}
+
+#[tokio::test]
+async fn messages_arrive_in_order_in_each_channel() {
+	let broker = Broker::start().await;
+	let bot = Bot::start(
+		&broker,
+		"link-a",
+		&[
+			(CHANNEL_A, GUILD_A),
+			(CHANNEL_B, GUILD_B),
+			(CHANNEL_C, GUILD_C),
+		],
+	)
+	.await;
+	let sent: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+	let unlimited = Limit {
+		messages: 0,
+		seconds: 1,
+	};
+
+	*bot.handler.rate_limits.lock().await = RateLimits::new(&RateLimit {
+		user: unlimited,
+		channel: unlimited,
+		peer: unlimited,
+	});
+	bot.ready().await;
+
+	for (i, content) in sent.iter().enumerate() {
+		bot.send(from_alice(1_400_000_000_000_000_100 + i as u64, content))
+			.await;
+	}
+
+	let executions = eventually("Not every message was mirrored", || async {
+		let executions: Vec<_> = bot
+			.discord
+			.requests()
+			.await
+			.into_iter()
+			.filter(|r| r.is_execution())
+			.collect();
+
+		(executions.len() == sent.len() * 2).then_some(executions)
+	})
+	.await;
+
+	for channel in [CHANNEL_B, CHANNEL_C] {
+		let received: Vec<String> = executions
+			.iter()
+			.filter(|r| r.response["channel_id"] == *channel.to_string())
+			.map(|r| r.body["content"].as_str().unwrap().to_owned())
+			.collect();
+
+		assert_eq!(received, sent, "Messages in {channel} were out of order");
+	}
+}
+
+#[tokio::test]
+async fn replies_quote_the_original() {
+	let (_broker, a, b) = two_bots().await;
+	let mut reply = from_alice(1_400_000_000_000_000_002, "I agree");
+	reply["referenced_message"] = message(
+		1_400_000_000_000_000_001,
+		CHANNEL_A,
+		user(MALLORY, "Mallory", false),
+		"Bots are great",
+	);
+
+	a.send(reply).await;
+
+	let execution = b
+		.discord
+		.wait_for("The reply was never relayed", |r| r.is_execution())
+		.await;
+	let quote = execution.body["embeds"][0]["description"].as_str().unwrap();
+
+	assert_eq!(execution.body["content"], "I agree");
+	assert!(quote.contains("Reply to"));
+	assert!(quote.contains("Bots are great"));
+}
+
+#[tokio::test]
+async fn edits_are_mirrored() {
+	let (_broker, bot) = one_bot().await;
+	let id = 1_400_000_000_000_000_003;
+
+	bot.send(from_alice(id, "Helo")).await;
+	bot.mirrored(id, 1).await;
+
+	let execution = bot
+		.discord
+		.wait_for("The message was never mirrored", |r| r.is_execution())
+		.await;
+
+	bot.edit(CHANNEL_A, id, "Hello").await;
+
+	let edit = bot
+		.discord
+		.wait_for("The mirror was never edited", |r| r.method == Method::PATCH)
+		.await;
+
+	assert!(edit
+		.path
+		.ends_with(&format!("/messages/{}", mirror_id(&execution))));
+	assert_eq!(edit.body["content"], "Hello");
+}
+
+#[tokio::test]
+async fn edits_are_relayed_to_other_bots() {
+	let (_broker, a, b) = two_bots().await;
+	let id = 1_400_000_000_000_000_007;
+
+	a.send(from_alice(id, "Helo")).await;
+	b.mirrored(id, 1).await;
+
+	let execution = b
+		.discord
+		.wait_for("The message was never relayed", |r| r.is_execution())
+		.await;
+
+	a.edit(CHANNEL_A, id, "Hello").await;
+
+	let edit = b
+		.discord
+		.wait_for("The mirror was never edited", |r| r.method == Method::PATCH)
+		.await;
+
+	assert!(edit
+		.path
+		.ends_with(&format!("/messages/{}", mirror_id(&execution))));
+	assert_eq!(edit.body["content"], "Hello");
+}
+
+#[tokio::test]
+async fn edits_are_filtered() {
+	let (_broker, bot) = one_bot().await;
+	let id = 1_400_000_000_000_000_008;
+	let rule = |name: &str, action, word: &str| FilterRule {
+		name: name.to_owned(),
+		action,
+		words: vec![word.to_owned()],
+		regex: None,
+	};
+
+	*bot.handler.content_filter.lock().await = ContentFilter::new(&FilterRules {
+		block_invites: None,
+		rules: vec![
+			rule("spam", FilterAction::Drop, "spam"),
+			rule("swearing", FilterAction::Redact, "heck"),
+		],
+	})
+	.unwrap();
+
+	bot.send(from_alice(id, "Hello")).await;
+	bot.mirrored(id, 1).await;
+	bot.edit(CHANNEL_A, id, "Buy spam").await;
+	bot.edit(CHANNEL_A, id, "What the heck").await;
+
+	// Edits are applied in order, so the dropped one would have been first.
+	let edit = bot
+		.discord
+		.wait_for("The mirror was never edited", |r| r.method == Method::PATCH)
+		.await;
+	sleep(Duration::from_millis(100)).await;
+
+	let edits = bot
+		.discord
+		.requests()
+		.await
+		.into_iter()
+		.filter(|r| r.method == Method::PATCH)
+		.count();
+
+	assert_eq!(edits, 1);
+	assert_eq!(edit.body["content"], "What the [redacted]");
+}
+
+#[tokio::test]
+async fn deleted_webhooks_are_recreated() {
+	let (_broker, bot) = one_bot().await;
+	let id = 1_400_000_000_000_000_009;
+	let webhooks = &bot.handler.webhooks;
+	let webhook_in_b = || async {
+		webhooks
+			.lock()
+			.await
+			.iter()
+			.find(|w| w.channel_id == Some(ChannelId(CHANNEL_B)))
+			.map(|w| w.id)
+	};
+
+	bot.send(from_alice(id, "Helo")).await;
+	bot.mirrored(id, 1).await;
+
+	let execution = bot
+		.discord
+		.wait_for("The message was never mirrored", |r| r.is_execution())
+		.await;
+	let deleted = webhook_in_b().await.unwrap();
+
+	bot.discord.delete_webhooks(CHANNEL_B).await;
+
+	// The mirror can't be edited without its webhook, which is how the bot
+	// finds out it was deleted.
+	bot.edit(CHANNEL_A, id, "Hello").await;
+
+	let recreated = eventually("The webhook was never recreated", || async {
+		webhook_in_b().await.filter(|w| *w != deleted)
+	})
+	.await;
+
+	// Deleting the old mirror doesn't need its webhook.
+	bot.delete(CHANNEL_A, GUILD_A, id).await;
+	bot.discord
+		.wait_for("The old mirror was never deleted", |r| {
+			r.method == Method::DELETE
+				&& r.path == format!("channels/{CHANNEL_B}/messages/{}", mirror_id(&execution))
+		})
+		.await;
+
+	bot.send(from_alice(1_400_000_000_000_000_010, "Still here"))
+		.await;
+	bot.discord
+		.wait_for("The new webhook was never used", |r| {
+			r.is_execution() && r.path.starts_with(&format!("webhooks/{recreated}/"))
+		})
+		.await;
+}
+
+#[tokio::test]
+async fn deletions_are_mirrored() {
+	let (_broker, bot) = one_bot().await;
+	let id = 1_400_000_000_000_000_004;
+
+	bot.send(from_alice(id, "Oops")).await;
+	bot.mirrored(id, 1).await;
+
+	let execution = bot
+		.discord
+		.wait_for("The message was never mirrored", |r| r.is_execution())
+		.await;
+
+	bot.delete(CHANNEL_A, GUILD_A, id).await;
+
+	let deletion = bot
+		.discord
+		.wait_for("The mirror was never deleted", |r| {
+			r.method == Method::DELETE
+		})
+		.await;
+
+	assert_eq!(
+		deletion.path,
+		format!("channels/{CHANNEL_B}/messages/{}", mirror_id(&execution))
+	);
+	assert!(bot
+		.handler
+		.message_cache
+		.lock()
+		.await
+		.get_entry(&MessageId(id))
+		.is_none());
+}
+
+#[tokio::test]
+async fn banned_users_are_not_relayed() {
+	let (_broker, a, b) = two_bots().await;
+
+	a.handler.ban_list.lock().await.list.insert(
+		UserId(MALLORY),
+		BanEntry {
+			reason: "Spam".to_owned(),
+			executor: UserId(ALICE),
+			ban_origin: GuildId(GUILD_A),
+			timestamp: Timestamp::now(),
+			expires_at: None,
+		},
+	);
+
+	a.send(message(
+		1_400_000_000_000_000_005,
+		CHANNEL_A,
+		user(MALLORY, "Mallory", false),
+		"Spam",
+	))
+	.await;
+	a.send(from_alice(1_400_000_000_000_000_006, "Hello")).await;
+
+	// Messages are relayed in order, so Mallory's would have arrived first.
+	b.discord
+		.wait_for("Alice's message was never relayed", |r| {
+			r.is_execution() && r.body["content"] == "Hello"
+		})
+		.await;
+	sleep(Duration::from_millis(100)).await;
+
+	let executions: Vec<_> = b
+		.discord
+		.requests()
+		.await
+		.into_iter()
+		.filter(|r| r.is_execution())
+		.collect();
+
+	assert_eq!(executions.len(), 1);
+}
+
+#[tokio::test]
+async fn expired_bans_are_lifted_by_direct_message() {
+	let broker = Broker::start().await;
+	let bot = Bot::start(&broker, "link-a", &[(CHANNEL_A, GUILD_A)]).await;
+
+	bot.handler.ban_list.lock().await.list.insert(
+		UserId(MALLORY),
+		BanEntry {
+			reason: "Spam".to_owned(),
+			executor: UserId(ALICE),
+			ban_origin: GuildId(GUILD_A),
+			timestamp: Timestamp::from_unix_timestamp(0).unwrap(),
+			expires_at: Some(Timestamp::from_unix_timestamp(60).unwrap()),
+		},
+	);
+	bot.ready().await;
+
+	let dm = bot
+		.discord
+		.wait_for("The DM channel was never created", |r| {
+			r.path == "users/@me/channels" && r.body["recipient_id"] == MALLORY
+		})
+		.await;
+	let dm_channel = dm.response["id"].as_str().unwrap().to_owned();
+	let notice = bot
+		.discord
+		.wait_for("The user was never told", |r| {
+			r.path == format!("channels/{dm_channel}/messages")
+		})
+		.await;
+
+	assert!(notice.body["content"]
+		.as_str()
+		.unwrap()
+		.contains("ban has expired"));
+	assert!(bot.handler.ban_list.lock().await.list.is_empty());
+}
@@ -0,0 +1,232 @@
+//! Runs the bot against a local MQTT broker and a fake Discord API, so the
+//! flows between bots can be tested without a network connection.
+//!
+//! The harness lives under `src/` behind `#[cfg(test)]` rather than in
+//! `tests/`, because the crate only has a binary target. Integration tests in
+//! `tests/` can't reach [`DiscordHandler`] or the link, so only the config and
+//! state files are tested there. The flows themselves are in `flows.rs`.
+
+use std::future::Future;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use serenity::client::bridge::gateway::ShardMessenger;
+use serenity::client::{Context, EventHandler};
+use serenity::futures::channel::mpsc;
+use serenity::http::HttpBuilder;
+use serenity::model::channel::Message;
+use serenity::model::gateway::Ready;
+use serenity::model::prelude::{ChannelId, GuildId, MessageId, MessageUpdateEvent};
+use serenity::prelude::{Mutex, RwLock, TypeMap};
+use tokio::time::{sleep, timeout};
+
+use self::broker::Broker;
+use self::discord::FakeDiscord;
+use crate::intergalactic_chat::config::Config;
+use crate::intergalactic_chat::data::DataDir;
+use crate::intergalactic_chat::discord::bot::DiscordHandler;
+use crate::intergalactic_chat::discord::link;
+use crate::intergalactic_chat::metrics::LinkMetrics;
+use crate::intergalactic_chat::mqtt::{connect, MqttStatus};
+
+pub mod broker;
+pub mod discord;
+mod flows;
+
+/// The user ID of every bot started by the harness.
+pub const BOT_ID: u64 = 1_000_000_000_000_000_001;
+
+/// The application ID of every bot started by the harness.
+const APPLICATION_ID: u64 = 1_000_000_000_000_000_002;
+
+/// How long to wait for something to happen before failing the test.
+const PATIENCE: Duration = Duration::from_secs(5);
+
+/// Used to give each bot its own data directory.
+static BOTS_STARTED: AtomicUsize = AtomicUsize::new(0);
+
+/// A bot connected to a [`Broker`], with its own [`FakeDiscord`]. Events are
+/// passed straight to its [`DiscordHandler`] instead of coming from the
+/// gateway.
+pub struct Bot {
+	pub handler: Arc<DiscordHandler>,
+	pub discord: FakeDiscord,
+	context: Context,
+}
+
+impl Bot {
+	/// Starts a bot linking `channels`, given as pairs of channel and guild IDs.
+	/// The bot isn't ready until [`Bot::ready`] is called.
+	pub async fn start(broker: &Broker, name: &str, channels: &[(u64, u64)]) -> Self {
+		let discord = FakeDiscord::start(channels).await;
+		let channel_list = channels
+			.iter()
+			.map(|(c, _)| c.to_string())
+			.collect::<Vec<_>>()
+			.join(", ");
+		let config: Config = toml::from_str(&format!(
+			r#"
+			[mqtt]
+			client_id = "{name}"
+			broker_ip = "127.0.0.1"
+			broker_port = {port}
+			topic = "harness/chat"
+
+			[discord]
+			bot_id = {BOT_ID}
+			channels = [{channel_list}]
+			token = "token"
+			"#,
+			port = broker.port,
+		))
+		.unwrap();
+
+		let data_dir = DataDir::new(std::env::temp_dir().join(format!(
+			"icl-harness-{}-{}",
+			process::id(),
+			BOTS_STARTED.fetch_add(1, Ordering::Relaxed)
+		)));
+		data_dir.create().unwrap();
+
+		let http = Arc::new(
+			HttpBuilder::new("token")
+				.proxy(&discord.url)
+				.unwrap()
+				.ratelimiter_disabled(true)
+				.application_id(APPLICATION_ID)
+				.build(),
+		);
+		let metrics = Arc::new(LinkMetrics::new(&config.mqtt.topic));
+		let mqtt_status = Arc::new(Mutex::new(MqttStatus::default()));
+		let (mq_client, routes) =
+			connect(&config.mqtt, Arc::clone(&mqtt_status), Arc::clone(&metrics)).await;
+		let handler = Arc::new(DiscordHandler::for_tests(
+			config,
+			mq_client,
+			routes,
+			data_dir,
+			mqtt_status,
+			metrics,
+		));
+
+		link::start(Arc::clone(&handler), Arc::clone(&http));
+
+		let context = Context {
+			data: Arc::new(RwLock::new(TypeMap::new())),
+			shard: ShardMessenger::new(mpsc::unbounded().0),
+			shard_id: 0,
+			http,
+		};
+
+		Self {
+			handler,
+			discord,
+			context,
+		}
+	}
+
+	/// Sends the bot a ready event, then waits for it to be connected to the
+	/// broker.
+	pub async fn ready(&self) {
+		let ready: Ready = serde_json::from_value(json!({
+			"v": 10,
+			"user": {
+				"id": BOT_ID.to_string(),
+				"username": "Link",
+				"discriminator": "0001",
+				"avatar": null,
+				"bot": true,
+				"verified": true,
+				"mfa_enabled": false,
+			},
+			"guilds": [],
+			"session_id": "session",
+			"application": { "id": APPLICATION_ID.to_string(), "flags": 0 },
+		}))
+		.unwrap();
+
+		self.handler.ready(self.context.clone(), ready).await;
+
+		eventually("The bot never connected to the broker", || async {
+			self.handler
+				.mqtt_status
+				.lock()
+				.await
+				.connected_since
+				.map(|_| ())
+		})
+		.await;
+	}
+
+	/// Sends the bot a message created event for `message`, see
+	/// [`discord::message`].
+	pub async fn send(&self, message: Value) {
+		let message: Message = serde_json::from_value(message).unwrap();
+
+		self.handler.message(self.context.clone(), message).await;
+	}
+
+	/// Sends the bot an event for the message `id` in `channel` being edited.
+	pub async fn edit(&self, channel: u64, id: u64, content: &str) {
+		let update: MessageUpdateEvent = serde_json::from_value(json!({
+			"id": id.to_string(),
+			"channel_id": channel.to_string(),
+			"content": content,
+		}))
+		.unwrap();
+
+		self.handler
+			.message_update(self.context.clone(), update)
+			.await;
+	}
+
+	/// Sends the bot an event for the message `id` in `channel` being deleted.
+	pub async fn delete(&self, channel: u64, guild: u64, id: u64) {
+		self.handler
+			.message_delete(
+				self.context.clone(),
+				ChannelId(channel),
+				MessageId(id),
+				Some(GuildId(guild)),
+			)
+			.await;
+	}
+
+	/// Waits for every mirror of the message `id` to be cached, so it can be
+	/// edited and deleted.
+	pub async fn mirrored(&self, id: u64, mirrors: usize) {
+		eventually("The message was never mirrored", || async {
+			self.handler
+				.message_cache
+				.lock()
+				.await
+				.get_entry(&MessageId(id))
+				.filter(|v| v.1.len() == mirrors)
+				.map(|_| ())
+		})
+		.await;
+	}
+}
+
+/// Polls `check` until it returns a value, panicking with `description` if it
+/// doesn't within five seconds.
+pub async fn eventually<T, F, Fut>(description: &str, mut check: F) -> T
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Option<T>>,
+{
+	let polled = timeout(PATIENCE, async {
+		loop {
+			if let Some(value) = check().await {
+				return value;
+			}
+
+			sleep(Duration::from_millis(10)).await;
+		}
+	});
+
+	polled.await.expect(description)
+}
@@ -0,0 +1,235 @@
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+/// Upper bounds of the webhook latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Why a message was not sent or shown.
+#[derive(Debug, Clone, Copy)]
+pub enum DropReason {
+	Banned,
+	Filtered,
+	Duplicate,
+	RateLimited,
+}
+
+impl DropReason {
+	const ALL: [DropReason; 4] = [
+		DropReason::Banned,
+		DropReason::Filtered,
+		DropReason::Duplicate,
+		DropReason::RateLimited,
+	];
+
+	fn label(&self) -> &'static str {
+		match self {
+			DropReason::Banned => "banned",
+			DropReason::Filtered => "filtered",
+			DropReason::Duplicate => "duplicate",
+			DropReason::RateLimited => "rate_limited",
+		}
+	}
+}
+
+/// Counters exposed to Prometheus. Every metric is labelled with the network,
+/// the MQTT topic the bot is on.
+#[derive(Debug, Default)]
+pub struct LinkMetrics {
+	network: String,
+	pub messages_published: AtomicU64,
+	pub messages_received: AtomicU64,
+	pub webhook_executions: AtomicU64,
+	pub webhook_failures: AtomicU64,
+	/// Cumulative counts for each of [`LATENCY_BUCKETS`].
+	webhook_latency_buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+	webhook_latency_micros: AtomicU64,
+	pub mqtt_reconnects: AtomicU64,
+	/// Times a handler fell behind and the connection to the broker was
+	/// paused for it.
+	pub mqtt_backpressure: AtomicU64,
+	pub cache_hits: AtomicU64,
+	pub cache_misses: AtomicU64,
+	dropped: [AtomicU64; DropReason::ALL.len()],
+}
+
+impl LinkMetrics {
+	pub fn new(network: &str) -> Self {
+		Self {
+			network: network.to_owned(),
+			..Default::default()
+		}
+	}
+
+	pub fn increment(counter: &AtomicU64) {
+		counter.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Records the time taken to execute a webhook, and whether it failed.
+	pub fn observe_webhook(&self, latency: Duration, failed: bool) {
+		Self::increment(&self.webhook_executions);
+
+		if failed {
+			Self::increment(&self.webhook_failures);
+		}
+
+		for (bucket, count) in LATENCY_BUCKETS.iter().zip(&self.webhook_latency_buckets) {
+			if latency.as_secs_f64() <= *bucket {
+				Self::increment(count);
+			}
+		}
+
+		self.webhook_latency_micros
+			.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+	}
+
+	/// Records a lookup of an edited or deleted message in the message cache.
+	pub fn observe_cache(&self, hit: bool) {
+		Self::increment(if hit {
+			&self.cache_hits
+		} else {
+			&self.cache_misses
+		});
+	}
+
+	pub fn dropped(&self, reason: DropReason) {
+		Self::increment(&self.dropped[reason as usize]);
+	}
+
+	/// Renders every metric in the Prometheus text format.
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		let network = &self.network;
+		let get = |c: &AtomicU64| c.load(Ordering::Relaxed);
+
+		let counters = [
+			(
+				"icl_messages_published_total",
+				"Messages sent from linked channels to the network.",
+				&self.messages_published,
+			),
+			(
+				"icl_messages_received_total",
+				"Messages received from other bots on the network.",
+				&self.messages_received,
+			),
+			(
+				"icl_webhook_failures_total",
+				"Webhook executions that returned an error.",
+				&self.webhook_failures,
+			),
+			(
+				"icl_mqtt_reconnects_total",
+				"Times the connection to the broker was re-established.",
+				&self.mqtt_reconnects,
+			),
+			(
+				"icl_mqtt_backpressure_total",
+				"Times a handler fell behind and the MQTT connection was paused for it.",
+				&self.mqtt_backpressure,
+			),
+			(
+				"icl_cache_hits_total",
+				"Edited or deleted messages found in the message cache.",
+				&self.cache_hits,
+			),
+			(
+				"icl_cache_misses_total",
+				"Edited or deleted messages not found in the message cache.",
+				&self.cache_misses,
+			),
+		];
+
+		for (name, help, counter) in counters {
+			writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter").unwrap();
+			writeln!(out, "{name}{{network=\"{network}\"}} {}", get(counter)).unwrap();
+		}
+
+		let name = "icl_messages_dropped_total";
+		writeln!(
+			out,
+			"# HELP {name} Messages that were not sent or shown, by reason.\n# TYPE {name} counter"
+		)
+		.unwrap();
+
+		for (reason, count) in DropReason::ALL.iter().zip(&self.dropped) {
+			writeln!(
+				out,
+				"{name}{{network=\"{network}\",reason=\"{}\"}} {}",
+				reason.label(),
+				get(count)
+			)
+			.unwrap();
+		}
+
+		let name = "icl_webhook_latency_seconds";
+		writeln!(
+			out,
+			"# HELP {name} Time taken to execute webhooks.\n# TYPE {name} histogram"
+		)
+		.unwrap();
+
+		for (bucket, count) in LATENCY_BUCKETS.iter().zip(&self.webhook_latency_buckets) {
+			writeln!(
+				out,
+				"{name}_bucket{{network=\"{network}\",le=\"{bucket}\"}} {}",
+				get(count)
+			)
+			.unwrap();
+		}
+
+		let executions = get(&self.webhook_executions);
+		writeln!(
+			out,
+			"{name}_bucket{{network=\"{network}\",le=\"+Inf\"}} {executions}"
+		)
+		.unwrap();
+		writeln!(
+			out,
+			"{name}_sum{{network=\"{network}\"}} {}",
+			get(&self.webhook_latency_micros) as f64 / 1_000_000.0
+		)
+		.unwrap();
+		writeln!(out, "{name}_count{{network=\"{network}\"}} {executions}").unwrap();
+
+		out
+	}
+}
+
+/// Serves the metrics on `GET /metrics` until the bot exits.
+pub async fn serve(address: SocketAddr, metrics: Arc<LinkMetrics>) {
+	let make_service = make_service_fn(move |_| {
+		let metrics = Arc::clone(&metrics);
+
+		async move {
+			Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+				let metrics = Arc::clone(&metrics);
+
+				async move {
+					let response = match (request.method(), request.uri().path()) {
+						(&Method::GET, "/metrics") => Response::builder()
+							.header("Content-Type", "text/plain; version=0.0.4")
+							.body(Body::from(metrics.render())),
+						_ => Response::builder()
+							.status(StatusCode::NOT_FOUND)
+							.body(Body::empty()),
+					};
+
+					Ok::<_, Infallible>(response.unwrap())
+				}
+			}))
+		}
+	});
+
+	println!("Serving metrics on http://{address}/metrics");
+
+	if let Err(e) = Server::bind(&address).serve(make_service).await {
+		println!("Metrics server error: {e}");
+	}
+}
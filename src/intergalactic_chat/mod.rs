@@ -1,3 +1,9 @@
+pub mod admin;
+pub mod cli;
 pub mod config;
+pub mod data;
 pub mod discord;
+#[cfg(test)]
+pub mod harness;
+pub mod metrics;
 pub mod mqtt;
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use rumqttc::Publish;
+use serenity::model::channel::Message;
+use serenity::prelude::Mutex;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::oneshot;
+
+use super::presence::presence_filter;
+use super::{deletion_topic, edit_topic, probe_topic};
+use crate::intergalactic_chat::config::Mqtt;
+use crate::intergalactic_chat::discord::deletions::MirrorDeletion;
+use crate::intergalactic_chat::discord::edits::MessageEdit;
+use crate::intergalactic_chat::metrics::LinkMetrics;
+
+/// How many publishes each handler can have waiting before the connection to
+/// the broker is paused until it catches up.
+const ROUTE_CAPACITY: usize = 100;
+
+/// The handler a publish is sent to, decided by its topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+	/// Messages sent in other linked channels.
+	Chat,
+	/// Edits of messages sent in linked channels.
+	Edit,
+	/// Moderator deletions of mirrored messages.
+	Deletion,
+	/// Heartbeats of the bots on the network.
+	Presence,
+	/// This bot's round-trip probes.
+	Probe,
+}
+
+impl Route {
+	pub fn label(&self) -> &'static str {
+		match self {
+			Route::Chat => "chat",
+			Route::Edit => "edit",
+			Route::Deletion => "deletion",
+			Route::Presence => "presence",
+			Route::Probe => "probe",
+		}
+	}
+}
+
+/// The receiving ends of [`Dispatcher`], one for each handler.
+pub struct Routes {
+	pub chat: Receiver<Message>,
+	pub edits: Receiver<MessageEdit>,
+	pub deletions: Receiver<MirrorDeletion>,
+	pub presence: Receiver<Publish>,
+	pub probes: Arc<Probes>,
+}
+
+/// The round-trip probes waiting to be received back from the broker, by
+/// payload.
+#[derive(Debug, Default)]
+pub struct Probes {
+	waiting: Mutex<HashMap<Vec<u8>, oneshot::Sender<Instant>>>,
+}
+
+impl Probes {
+	/// Waits for a probe with `nonce` as its payload. The returned receiver
+	/// gets the time it arrived.
+	pub async fn expect(&self, nonce: &str) -> oneshot::Receiver<Instant> {
+		let (sender, receiver) = oneshot::channel();
+
+		self.waiting
+			.lock()
+			.await
+			.insert(nonce.as_bytes().to_vec(), sender);
+
+		receiver
+	}
+
+	/// Stops waiting for the probe with `nonce` as its payload.
+	pub async fn forget(&self, nonce: &str) {
+		self.waiting.lock().await.remove(nonce.as_bytes());
+	}
+
+	async fn arrived(&self, payload: &[u8]) {
+		if let Some(sender) = self.waiting.lock().await.remove(payload) {
+			sender.send(Instant::now()).ok();
+		}
+	}
+}
+
+/// Sends each publish received from the broker to the handler for its topic.
+/// Publishes are never dropped: when a handler falls behind, the dispatcher
+/// waits for it, which stops the event loop from being polled until it has
+/// caught up.
+pub struct Dispatcher {
+	chat_topic: String,
+	edit_topic: String,
+	deletion_topic: String,
+	probe_topic: String,
+	/// The presence topics, without the client ID at the end.
+	presence_prefix: String,
+	chat: Sender<Message>,
+	edits: Sender<MessageEdit>,
+	deletions: Sender<MirrorDeletion>,
+	presence: Sender<Publish>,
+	probes: Arc<Probes>,
+	metrics: Arc<LinkMetrics>,
+}
+
+impl Dispatcher {
+	pub fn new(config: &Mqtt, metrics: Arc<LinkMetrics>) -> (Self, Routes) {
+		let (chat, chat_receiver) = channel(ROUTE_CAPACITY);
+		let (edits, edits_receiver) = channel(ROUTE_CAPACITY);
+		let (deletions, deletions_receiver) = channel(ROUTE_CAPACITY);
+		let (presence, presence_receiver) = channel(ROUTE_CAPACITY);
+		let probes = Arc::new(Probes::default());
+
+		(
+			Self {
+				chat_topic: config.topic.to_owned(),
+				edit_topic: edit_topic(config),
+				deletion_topic: deletion_topic(config),
+				probe_topic: probe_topic(config),
+				presence_prefix: presence_filter(config).trim_end_matches('+').to_owned(),
+				chat,
+				edits,
+				deletions,
+				presence,
+				probes: Arc::clone(&probes),
+				metrics,
+			},
+			Routes {
+				chat: chat_receiver,
+				edits: edits_receiver,
+				deletions: deletions_receiver,
+				presence: presence_receiver,
+				probes,
+			},
+		)
+	}
+
+	/// The route of publishes to `topic`, `None` if this bot didn't subscribe
+	/// to it.
+	pub fn route(&self, topic: &str) -> Option<Route> {
+		if topic == self.chat_topic {
+			Some(Route::Chat)
+		} else if topic == self.edit_topic {
+			Some(Route::Edit)
+		} else if topic == self.deletion_topic {
+			Some(Route::Deletion)
+		} else if topic == self.probe_topic {
+			Some(Route::Probe)
+		} else if topic.starts_with(&self.presence_prefix) {
+			Some(Route::Presence)
+		} else {
+			None
+		}
+	}
+
+	/// Sends `publish` to its handler, waiting if the handler is behind.
+	/// Payloads that can't be decoded are logged and skipped.
+	pub async fn dispatch(&self, publish: Publish) {
+		let route = match self.route(&publish.topic) {
+			Some(r) => r,
+			None => {
+				println!("Ignoring a message on unexpected topic {}", publish.topic);
+				return;
+			}
+		};
+
+		match route {
+			Route::Chat => match serde_json::from_slice(&publish.payload) {
+				Ok(m) => self.forward(route, &self.chat, m).await,
+				Err(e) => println!("Ignoring an invalid chat message: {e}"),
+			},
+			Route::Edit => match serde_json::from_slice(&publish.payload) {
+				Ok(e) => self.forward(route, &self.edits, e).await,
+				Err(e) => println!("Ignoring an invalid edit: {e}"),
+			},
+			Route::Deletion => match serde_json::from_slice(&publish.payload) {
+				Ok(d) => self.forward(route, &self.deletions, d).await,
+				Err(e) => println!("Ignoring an invalid deletion: {e}"),
+			},
+			Route::Presence => self.forward(route, &self.presence, publish).await,
+			Route::Probe => self.probes.arrived(&publish.payload).await,
+		}
+	}
+
+	async fn forward<T>(&self, route: Route, sender: &Sender<T>, value: T) {
+		let value = match sender.try_send(value) {
+			Ok(()) => return,
+			Err(TrySendError::Full(v)) => v,
+			// The handler has stopped, so there is nobody to wait for.
+			Err(TrySendError::Closed(_)) => return,
+		};
+
+		let start = Instant::now();
+		LinkMetrics::increment(&self.metrics.mqtt_backpressure);
+		println!(
+			"The {} handler is behind, pausing the MQTT connection",
+			route.label()
+		);
+
+		if sender.send(value).await.is_ok() {
+			println!(
+				"The {} handler caught up after {:#?}",
+				route.label(),
+				start.elapsed()
+			);
+		}
+	}
+}
@@ -1,19 +1,145 @@
-use rumqttc::{Event, EventLoop};
-use tokio::sync::broadcast::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-/// Continually polls the [`rumqttc::EventLoop`] and sends the results to a
-/// [`tokio::sync::broadcast::Sender`].
+use rumqttc::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, QoS};
+use serenity::prelude::Mutex;
+use tokio::task;
+use tokio::time::timeout;
+
+use crate::intergalactic_chat::config::Mqtt;
+use crate::intergalactic_chat::metrics::LinkMetrics;
+
+pub mod dispatcher;
+pub mod presence;
+
+use dispatcher::{Dispatcher, Probes, Routes};
+use presence::{last_will, presence_filter};
+
+/// The state of the connection to the broker, as seen by [`poll_event_loop`].
+#[derive(Debug, Clone, Default)]
+pub struct MqttStatus {
+	/// When the current connection was acknowledged by the broker, `None` if
+	/// not connected.
+	pub connected_since: Option<Instant>,
+	/// The number of times the connection was acknowledged, after the first.
+	pub reconnects: u32,
+	pub last_error: Option<String>,
+}
+
+/// The topic used to measure the round-trip time to the broker. Only this bot
+/// subscribes to it.
+pub fn probe_topic(config: &Mqtt) -> String {
+	format!("{}/probe/{}", config.topic, config.client_id)
+}
+
+/// The topic moderator deletions of mirrored messages are published to, see
+/// [`MirrorDeletion`](crate::intergalactic_chat::discord::deletions::MirrorDeletion).
+pub fn deletion_topic(config: &Mqtt) -> String {
+	format!("{}/delete", config.topic)
+}
+
+/// The topic edits of messages sent in linked channels are published to, see
+/// [`MessageEdit`](crate::intergalactic_chat::discord::edits::MessageEdit).
+pub fn edit_topic(config: &Mqtt) -> String {
+	format!("{}/edit", config.topic)
+}
+
+/// Every topic the bot subscribes to, and the QoS of each subscription.
+pub fn subscriptions(config: &Mqtt) -> Vec<(String, QoS)> {
+	vec![
+		(config.topic.to_owned(), QoS::AtMostOnce),
+		(edit_topic(config), QoS::AtLeastOnce),
+		(deletion_topic(config), QoS::AtLeastOnce),
+		(probe_topic(config), QoS::AtMostOnce),
+		(presence_filter(config), QoS::AtLeastOnce),
+	]
+}
+
+/// Connects to the broker, subscribing to every topic the bot uses, and starts
+/// polling the connection in the background.
+pub async fn connect(
+	config: &Mqtt, status: Arc<Mutex<MqttStatus>>, metrics: Arc<LinkMetrics>,
+) -> (AsyncClient, Routes) {
+	let mut options = MqttOptions::new(&config.client_id, &config.broker_ip, config.broker_port);
+	options.set_keep_alive(Duration::from_secs(5));
+	options.set_last_will(last_will(config));
+
+	let (client, event_loop) = AsyncClient::new(options, 10);
+	let (dispatcher, routes) = Dispatcher::new(config, Arc::clone(&metrics));
+
+	for (topic, qos) in subscriptions(config) {
+		client
+			.subscribe(topic, qos)
+			.await
+			.expect("Error creating MQTT subscription");
+	}
+
+	task::spawn(poll_event_loop(event_loop, dispatcher, status, metrics));
+
+	(client, routes)
+}
+
+/// Measures the time taken for a message published to the probe topic to be
+/// received back from the broker. Returns `None` if it doesn't arrive within
+/// five seconds.
+pub async fn round_trip(
+	client: &AsyncClient, probes: &Probes, topic: &str, nonce: &str,
+) -> Option<Duration> {
+	let arrival = probes.expect(nonce).await;
+	let start = Instant::now();
+
+	if client
+		.publish(topic, QoS::AtMostOnce, false, nonce.as_bytes().to_vec())
+		.await
+		.is_err()
+	{
+		probes.forget(nonce).await;
+		return None;
+	}
+
+	match timeout(Duration::from_secs(5), arrival).await {
+		Ok(Ok(arrived)) => Some(arrived - start),
+		_ => {
+			probes.forget(nonce).await;
+			None
+		}
+	}
+}
+
+/// Continually polls the [`rumqttc::EventLoop`], keeping `status` up to date
+/// and passing received publishes to `dispatcher`.
 // TODO: Needs proper error handling.
-pub async fn poll_event_loop(mut event_loop: EventLoop, sender: Sender<Event>) {
+pub async fn poll_event_loop(
+	mut event_loop: EventLoop, dispatcher: Dispatcher, status: Arc<Mutex<MqttStatus>>,
+	metrics: Arc<LinkMetrics>,
+) {
+	let mut connected_before = false;
+
 	loop {
 		let event = event_loop.poll().await;
 
-		match &event {
-			Ok(v) => {
-				let _ = sender.send(v.to_owned());
+		match event {
+			Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+				let mut status = status.lock().await;
+
+				status.connected_since = Some(Instant::now());
+
+				if connected_before {
+					status.reconnects += 1;
+					LinkMetrics::increment(&metrics.mqtt_reconnects);
+				}
+
+				connected_before = true;
 			}
+			Ok(Event::Incoming(Incoming::Publish(p))) => dispatcher.dispatch(p).await,
+			// Pings and acknowledgements are handled by the event loop.
+			Ok(_) => (),
 			Err(e) => {
 				println!("MQTT error: {e:?}");
+
+				let mut status = status.lock().await;
+				status.connected_since = None;
+				status.last_error = Some(e.to_string());
 			}
 		};
 	}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rumqttc::{AsyncClient, LastWill, Publish, QoS};
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::GuildId;
+use serenity::prelude::Mutex;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::sleep;
+
+use crate::intergalactic_chat::config::Mqtt;
+
+/// Published by every bot on the network to its presence topic, so other bots
+/// know it is online.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Heartbeat {
+	pub client_id: String,
+	pub bot_id: u64,
+	pub name: String,
+	pub version: String,
+	pub channels: Vec<LinkedChannel>,
+	/// Seconds since the bot started.
+	pub uptime: u64,
+}
+
+/// The names of a linked channel and the guild it is in.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LinkedChannel {
+	pub guild: String,
+	pub channel: String,
+	/// The ID of the guild, so received messages can be matched to the bot
+	/// that sent them. Missing from the heartbeats of older bots.
+	#[serde(default)]
+	pub guild_id: Option<GuildId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Peer {
+	/// The last heartbeat received, `None` if the bot went offline before one
+	/// was seen.
+	pub heartbeat: Option<Heartbeat>,
+	pub last_seen: Instant,
+	/// Set to `false` when the broker publishes the bot's last will.
+	pub connected: bool,
+}
+
+/// Every bot seen on the network, keyed by MQTT client ID.
+#[derive(Debug, Clone)]
+pub struct PeerDirectory {
+	/// How often peers are expected to publish heartbeats.
+	interval: Duration,
+	peers: HashMap<String, Peer>,
+}
+
+impl PeerDirectory {
+	pub fn new(interval: Duration) -> Self {
+		Self {
+			interval,
+			peers: HashMap::new(),
+		}
+	}
+
+	/// Updates the directory from a message on a presence topic. An empty
+	/// payload is the last will of a bot that disconnected.
+	pub fn handle(&mut self, client_id: &str, payload: &[u8]) {
+		if payload.is_empty() {
+			if let Some(peer) = self.peers.get_mut(client_id) {
+				peer.connected = false;
+			}
+
+			return;
+		}
+
+		match serde_json::from_slice::<Heartbeat>(payload) {
+			Ok(heartbeat) => {
+				self.peers.insert(
+					client_id.to_owned(),
+					Peer {
+						heartbeat: Some(heartbeat),
+						last_seen: Instant::now(),
+						connected: true,
+					},
+				);
+			}
+			Err(e) => println!("Invalid heartbeat from {client_id}: {e}"),
+		}
+	}
+
+	/// Whether `peer` is online. Peers that have missed three heartbeats are
+	/// considered offline even if the broker hasn't noticed yet.
+	pub fn is_online(&self, peer: &Peer) -> bool {
+		peer.connected && peer.last_seen.elapsed() < self.interval * 3
+	}
+
+	/// The client ID of the bot whose last heartbeat lists a channel in
+	/// `guild`.
+	pub fn client_for_guild(&self, guild: GuildId) -> Option<&str> {
+		self.peers
+			.iter()
+			.find(|(_, p)| {
+				p.heartbeat
+					.as_ref()
+					.is_some_and(|h| h.channels.iter().any(|c| c.guild_id == Some(guild)))
+			})
+			.map(|(id, _)| id.as_str())
+	}
+
+	/// Every peer except the bot with the client ID `own_id`, online peers
+	/// first.
+	pub fn peers(&self, own_id: &str) -> Vec<(&String, &Peer)> {
+		let mut peers: Vec<_> = self.peers.iter().filter(|(id, _)| *id != own_id).collect();
+		peers.sort_by_key(|(id, p)| (!self.is_online(p), id.to_owned()));
+
+		peers
+	}
+}
+
+/// The topic this bot publishes heartbeats to.
+pub fn presence_topic(config: &Mqtt) -> String {
+	format!("{}/presence/{}", config.topic, config.client_id)
+}
+
+/// The topic filter matching the presence topics of every bot.
+pub fn presence_filter(config: &Mqtt) -> String {
+	format!("{}/presence/+", config.topic)
+}
+
+/// A last will which clears this bot's retained heartbeat, marking it as
+/// offline when the broker loses the connection.
+pub fn last_will(config: &Mqtt) -> LastWill {
+	LastWill::new(presence_topic(config), Vec::new(), QoS::AtLeastOnce, true)
+}
+
+/// Publishes a retained heartbeat every `presence_interval` seconds, once
+/// `heartbeat` has been filled in with this bot's details.
+pub async fn publish_heartbeats(
+	client: AsyncClient, config: Mqtt, heartbeat: Arc<Mutex<Option<Heartbeat>>>,
+) {
+	let started = Instant::now();
+	let topic = presence_topic(&config);
+	let interval = Duration::from_secs(config.presence_interval);
+
+	loop {
+		let payload = heartbeat.lock().await.as_mut().map(|h| {
+			h.uptime = started.elapsed().as_secs();
+
+			serde_json::to_vec(h).unwrap()
+		});
+
+		match payload {
+			Some(payload) => {
+				if let Err(e) = client
+					.publish(&topic, QoS::AtLeastOnce, true, payload)
+					.await
+				{
+					println!("Error publishing heartbeat: {e}");
+				}
+
+				sleep(interval).await;
+			}
+			// Not ready yet, check again soon so the first heartbeat isn't delayed
+			// by a whole interval.
+			None => sleep(Duration::from_secs(1)).await,
+		}
+	}
+}
+
+/// Keeps `directory` up to date with the heartbeats published on the network.
+pub async fn track_peers(
+	mut receiver: Receiver<Publish>, config: Mqtt, directory: Arc<Mutex<PeerDirectory>>,
+) {
+	let prefix = format!("{}/presence/", config.topic);
+
+	while let Some(p) = receiver.recv().await {
+		if let Some(client_id) = p.topic.strip_prefix(&prefix) {
+			directory.lock().await.handle(client_id, &p.payload);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn heartbeat(client_id: &str, guild_id: Option<GuildId>) -> Vec<u8> {
+		serde_json::to_vec(&Heartbeat {
+			client_id: client_id.to_owned(),
+			bot_id: 1,
+			name: client_id.to_owned(),
+			version: "1.0.0".to_owned(),
+			channels: vec![LinkedChannel {
+				guild: "Guild".to_owned(),
+				channel: "link".to_owned(),
+				guild_id,
+			}],
+			uptime: 0,
+		})
+		.unwrap()
+	}
+
+	#[test]
+	fn guilds_are_matched_to_the_bot_that_lists_them() {
+		let mut directory = PeerDirectory::new(Duration::from_secs(30));
+		directory.handle("alpha", &heartbeat("alpha", Some(GuildId(1))));
+		directory.handle("beta", &heartbeat("beta", Some(GuildId(2))));
+
+		assert_eq!(directory.client_for_guild(GuildId(1)), Some("alpha"));
+		assert_eq!(directory.client_for_guild(GuildId(2)), Some("beta"));
+		assert_eq!(directory.client_for_guild(GuildId(3)), None);
+	}
+
+	#[test]
+	fn heartbeats_without_guild_ids_are_still_read() {
+		let mut directory = PeerDirectory::new(Duration::from_secs(30));
+		directory.handle(
+			"old",
+			br#"{"client_id":"old","bot_id":1,"name":"Old","version":"0.1.0","channels":[{"guild":"Guild","channel":"link"}],"uptime":5}"#,
+		);
+
+		assert_eq!(directory.peers("self").len(), 1);
+		assert_eq!(directory.client_for_guild(GuildId(1)), None);
+	}
+}
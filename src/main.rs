@@ -1,62 +1,213 @@
+use std::process::exit;
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::intergalactic_chat::discord::bot::DiscordHandler;
 use crate::intergalactic_chat::discord::cache::MessageCache;
+use intergalactic_chat::admin;
+use intergalactic_chat::cli::{self, Cli, Command};
 use intergalactic_chat::config::Config;
+use intergalactic_chat::data::DataDir;
 use intergalactic_chat::discord::bans::BanList;
-use intergalactic_chat::mqtt::poll_event_loop;
-use rumqttc::{AsyncClient, Event, MqttOptions, QoS};
+use intergalactic_chat::discord::filter::ContentFilter;
+use intergalactic_chat::discord::link::{self, Startup};
+use intergalactic_chat::discord::mod_log::ModLog;
+use intergalactic_chat::discord::mutes::MuteList;
+use intergalactic_chat::discord::rate_limit::RateLimits;
+use intergalactic_chat::discord::status::{LinkStatus, ShardManagerContainer};
+use intergalactic_chat::discord::webhooks::WebhookHealth;
+use intergalactic_chat::metrics::{self, LinkMetrics};
+use intergalactic_chat::mqtt::presence::{publish_heartbeats, track_peers, PeerDirectory};
+use intergalactic_chat::mqtt::{connect, MqttStatus};
 use serenity::prelude::*;
-use tokio::sync::broadcast;
 use tokio::task;
 
 mod intergalactic_chat;
 
 #[tokio::main]
 async fn main() {
-	let config = Config::initialize("config.toml").expect("Failed to initialize the config");
-	let message_cache = Arc::new(Mutex::new(MessageCache::initialize(".cache", 100)));
-	let ban_list = Arc::new(Mutex::new(BanList::initialize(".bans")));
-
-	let mut mq_options = MqttOptions::new(
-		&config.mqtt.client_id,
-		&config.mqtt.broker_ip,
-		config.mqtt.broker_port,
-	);
-	mq_options.set_keep_alive(Duration::from_secs(5));
-	let (mq_client, mq_event_loop) = AsyncClient::new(mq_options, 10);
-	let (event_sender, event_receiver) = broadcast::channel::<Event>(10);
-	let topic = config.mqtt.topic.clone();
-	let mq_client = task::spawn(async move {
-		mq_client
-			.subscribe(topic, QoS::AtMostOnce)
-			.await
-			.expect("Error creating MQTT subscription");
-
-		task::spawn(async move {
-			poll_event_loop(mq_event_loop, event_sender).await;
-		});
-
-		mq_client
-	})
+	let cli = match Cli::parse(std::env::args().skip(1)) {
+		Ok(cli) => cli,
+		Err(e) => {
+			eprintln!("{e}\n\n{}", cli::USAGE);
+			exit(2)
+		}
+	};
+
+	let result = match cli.command {
+		Command::Run => run(&cli.config, cli.data_dir).await,
+		Command::Init { force } => cli::init(&cli.config, force),
+		Command::CheckConfig => cli::check_config(&cli.config),
+		Command::BansList | Command::BansAdd { .. } | Command::BansRemove { .. } => cli::bans(&cli),
+		Command::Keygen => {
+			cli::keygen();
+			Ok(())
+		}
+		Command::Help => {
+			println!("{}", cli::USAGE);
+			Ok(())
+		}
+	};
+
+	if let Err(e) = result {
+		eprintln!("{e}");
+		exit(1)
+	}
+}
+
+async fn run(config_path: &str, data_dir: DataDir) -> Result<(), String> {
+	let config = Config::initialize(config_path)?;
+	data_dir.create()?;
+	let shared_config = Arc::new(RwLock::new(config.to_owned()));
+	let message_cache = Arc::new(Mutex::new(MessageCache::initialize(
+		&data_dir.cache(),
+		config.cache.size,
+		config.cache.max_age(),
+	)));
+	let ban_list = Arc::new(Mutex::new(BanList::initialize(&data_dir.bans())));
+	let mute_list = Arc::new(Mutex::new(MuteList::initialize(&data_dir.mutes())));
+	let rate_limits = Arc::new(Mutex::new(RateLimits::new(&config.rate_limit)));
+	let content_filter = Arc::new(Mutex::new(ContentFilter::initialize(&config.filter.rules)?));
+
+	let filter_config = Arc::clone(&shared_config);
+	let reloaded_filter = Arc::clone(&content_filter);
+	task::spawn(async move {
+		loop {
+			tokio::time::sleep(Duration::from_secs(5)).await;
+
+			// The path can change when the config is reloaded.
+			let filter_rules = filter_config.read().await.filter.rules.to_owned();
+
+			match reloaded_filter
+				.lock()
+				.await
+				.reload_if_changed(&filter_rules)
+			{
+				Ok(true) => println!("Reloaded filter rules from {filter_rules}"),
+				Ok(false) => (),
+				Err(e) => println!("Error reloading filter rules, keeping the old ones: {e}"),
+			}
+		}
+	});
+
+	let snapshot_config = Arc::clone(&shared_config);
+	let snapshot_cache = Arc::clone(&message_cache);
+	let cache_path = data_dir.cache();
+	task::spawn(async move {
+		loop {
+			// The interval can change when the config is reloaded.
+			let interval = snapshot_config.read().await.cache.snapshot_interval;
+
+			if interval == 0 {
+				tokio::time::sleep(Duration::from_secs(5)).await;
+				continue;
+			}
+
+			tokio::time::sleep(Duration::from_secs(interval)).await;
+
+			if let Err(e) = snapshot_cache.lock().await.snapshot(&cache_path) {
+				println!("Error saving the message cache: {e}");
+			}
+		}
+	});
+
+	let link_metrics = Arc::new(LinkMetrics::new(&config.mqtt.topic));
+
+	if config.metrics.enabled {
+		let address = config
+			.metrics
+			.address
+			.parse()
+			.map_err(|_| format!("Invalid metrics address {}", config.metrics.address))?;
+
+		task::spawn(metrics::serve(address, Arc::clone(&link_metrics)));
+	}
+
+	let mqtt_status = Arc::new(Mutex::new(MqttStatus::default()));
+	let (mq_client, routes) = connect(
+		&config.mqtt,
+		Arc::clone(&mqtt_status),
+		Arc::clone(&link_metrics),
+	)
 	.await;
+	let heartbeat = Arc::new(Mutex::new(None));
+	let peer_directory = Arc::new(Mutex::new(PeerDirectory::new(Duration::from_secs(
+		config.mqtt.presence_interval,
+	))));
+
+	task::spawn(publish_heartbeats(
+		mq_client.clone(),
+		config.mqtt.clone(),
+		Arc::clone(&heartbeat),
+	));
+	task::spawn(track_peers(
+		routes.presence,
+		config.mqtt.clone(),
+		Arc::clone(&peer_directory),
+	));
 
 	let intents = GatewayIntents::GUILD_MESSAGES
 		| GatewayIntents::DIRECT_MESSAGES
 		| GatewayIntents::MESSAGE_CONTENT;
+	let handler = Arc::new(DiscordHandler {
+		mq_client,
+		mq_chat: Mutex::new(routes.chat),
+		mq_edits: Mutex::new(routes.edits),
+		mq_deletions: Mutex::new(routes.deletions),
+		mq_probes: routes.probes,
+		config: shared_config,
+		config_path: config_path.to_owned(),
+		data_dir,
+		message_cache,
+		ban_list,
+		content_filter,
+		rate_limits,
+		mod_log: Arc::new(Mutex::new(ModLog::default())),
+		mute_list,
+		mqtt_status,
+		link_status: Arc::new(Mutex::new(LinkStatus::new())),
+		webhooks: Arc::new(Mutex::new(Vec::new())),
+		webhook_health: Arc::new(WebhookHealth::default()),
+		heartbeat,
+		peer_directory,
+		metrics: link_metrics,
+		startup: Startup::default(),
+	});
 	let mut discord_client = Client::builder(&config.discord.token, intents)
-		.event_handler(DiscordHandler {
-			mq_client: mq_client.expect("Threading error"),
-			mq_event_receiver: event_receiver,
-			config,
-			message_cache,
-			ban_list,
-		})
+		.event_handler_arc(Arc::clone(&handler))
+		.await
+		.map_err(|e| format!("Error creating Discord client: {e}"))?;
+
+	if config.admin.enabled {
+		if config.admin.token.is_empty() {
+			return Err("The admin API requires a token, set one in the [admin] table".to_owned());
+		}
+
+		let address = config
+			.admin
+			.address
+			.parse()
+			.map_err(|_| format!("Invalid admin API address {}", config.admin.address))?;
+
+		task::spawn(admin::serve(
+			address,
+			config.admin.token.to_owned(),
+			Arc::clone(&handler),
+			Arc::clone(&discord_client.cache_and_http.http),
+		));
+	}
+
+	link::start(
+		Arc::clone(&handler),
+		Arc::clone(&discord_client.cache_and_http.http),
+	);
+	discord_client
+		.data
+		.write()
 		.await
-		.expect("Error creating Discord client");
+		.insert::<ShardManagerContainer>(Arc::clone(&discord_client.shard_manager));
 	discord_client
 		.start()
 		.await
-		.expect("Failed to start Discord client");
+		.map_err(|e| format!("Failed to start Discord client: {e}"))
 }
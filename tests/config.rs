@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn fixture(name: &str) -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/fixtures/config")
+		.join(name)
+}
+
+fn check_config(path: &Path) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_discord-intergalactic-chat-link"))
+		.arg("check-config")
+		.arg("--config")
+		.arg(path)
+		.output()
+		.expect("Failed to run the binary")
+}
+
+/// Checks `name` fails, returning its stderr.
+fn check_invalid(name: &str) -> String {
+	let output = check_config(&fixture(name));
+
+	assert_eq!(output.status.code(), Some(1), "{name} should be invalid");
+
+	String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn valid_config_passes() {
+	let output = check_config(&fixture("valid.toml"));
+	let stdout = String::from_utf8(output.stdout).unwrap();
+
+	assert!(
+		output.status.success(),
+		"{}",
+		String::from_utf8_lossy(&output.stderr)
+	);
+	assert!(stdout.contains("linking 2 channels"), "{stdout}");
+}
+
+#[test]
+fn default_template_is_refused() {
+	let dir = std::env::temp_dir().join(format!("icl-config-test-{}", std::process::id()));
+	let path = dir.join("config.toml");
+	std::fs::create_dir_all(&dir).unwrap();
+	let _ = std::fs::remove_file(&path);
+
+	let init = Command::new(env!("CARGO_BIN_EXE_discord-intergalactic-chat-link"))
+		.arg("init")
+		.arg("--config")
+		.arg(&path)
+		.output()
+		.unwrap();
+	assert!(init.status.success());
+
+	let output = check_config(&path);
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	std::fs::remove_dir_all(&dir).unwrap();
+
+	assert_eq!(output.status.code(), Some(1));
+	assert!(
+		stderr.contains("`config` is still the default template"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn placeholder_token_is_reported() {
+	let stderr = check_invalid("placeholder_token.toml");
+
+	assert!(stderr.contains("has 1 problem:"), "{stderr}");
+	assert!(
+		stderr.contains("line 10: `discord.token` is a placeholder"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn bad_channels_are_reported_on_their_lines() {
+	let stderr = check_invalid("bad_channels.toml");
+
+	assert!(stderr.contains("has 2 problems"), "{stderr}");
+	assert!(
+		stderr.contains("line 11: `discord.channels` contains the channel ID 0"),
+		"{stderr}"
+	);
+	assert!(
+		stderr.contains(
+			"line 12: `discord.channels` contains the channel 223456789012345678 more than once"
+		),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn wildcard_topic_is_reported() {
+	let stderr = check_invalid("wildcard_topic.toml");
+
+	assert!(
+		stderr.contains("line 5: `mqtt.topic` \"intergalactic/#\" contains a wildcard"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn old_filter_mod_log_channel_is_reported() {
+	let stderr = check_invalid("filter_mod_log_channel.toml");
+
+	assert!(
+		stderr.contains("line 14: `filter.mod_log_channel` has been replaced, remove it and add 323456789012345678 to `discord.mod_log_channels` instead"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn admin_problems_are_all_reported() {
+	let stderr = check_invalid("admin_without_token.toml");
+
+	assert!(stderr.contains("has 2 problems"), "{stderr}");
+	assert!(
+		stderr.contains("line 15: `admin.address` \"localhost\" is not an address and port"),
+		"{stderr}"
+	);
+	assert!(stderr.contains("`admin.token` is empty"), "{stderr}");
+}
+
+#[test]
+fn invalid_filter_rules_are_reported() {
+	let stderr = check_invalid("invalid_filter_rules.toml");
+
+	assert!(stderr.contains("has 1 problem:"), "{stderr}");
+	assert!(
+		stderr.contains("line 14: `filter.rules` points to tests/fixtures/config/invalid_filter_rules.rules, whose rules are invalid: Rule \"both\" must have either `words` or `regex`"),
+		"{stderr}"
+	);
+}
+
+#[test]
+fn invalid_toml_is_reported() {
+	let stderr = check_invalid("invalid_toml.toml");
+
+	assert!(stderr.contains("invalid_toml.toml is invalid"), "{stderr}");
+	assert!(stderr.contains("line 4"), "{stderr}");
+}
+
+#[test]
+fn missing_config_is_reported() {
+	let stderr = check_invalid("missing.toml");
+
+	assert!(stderr.contains("Error trying to read"), "{stderr}");
+}
+
+#[test]
+fn environment_overrides_the_config() {
+	let token = std::env::temp_dir().join(format!("icl-token-test-{}", std::process::id()));
+	std::fs::write(&token, "MTIz.GaBcDe.abcdef\n").unwrap();
+
+	let output = Command::new(env!("CARGO_BIN_EXE_discord-intergalactic-chat-link"))
+		.arg("check-config")
+		.arg("--config")
+		.arg(fixture("placeholder_token.toml"))
+		.env("ICL_DISCORD_TOKEN_FILE", &token)
+		.env("ICL_DISCORD_CHANNELS", "1, 2, 3")
+		.env("ICL_RATE_LIMIT_USER_MESSAGES", "10")
+		.output()
+		.unwrap();
+	std::fs::remove_file(&token).unwrap();
+	let stdout = String::from_utf8(output.stdout).unwrap();
+
+	assert!(
+		output.status.success(),
+		"{}",
+		String::from_utf8_lossy(&output.stderr)
+	);
+	assert!(stdout.contains("linking 3 channels"), "{stdout}");
+	assert!(
+		stdout.contains("discord.token from ICL_DISCORD_TOKEN_FILE"),
+		"{stdout}"
+	);
+	assert!(
+		stdout.contains("rate_limit.user.messages from ICL_RATE_LIMIT_USER_MESSAGES"),
+		"{stdout}"
+	);
+}
+
+#[test]
+fn environment_problems_are_reported() {
+	let run = |vars: &[(&str, &str)]| {
+		let output = Command::new(env!("CARGO_BIN_EXE_discord-intergalactic-chat-link"))
+			.arg("check-config")
+			.arg("--config")
+			.arg(fixture("valid.toml"))
+			.envs(vars.iter().copied())
+			.output()
+			.unwrap();
+
+		assert_eq!(output.status.code(), Some(1));
+		String::from_utf8(output.stderr).unwrap()
+	};
+
+	let stderr = run(&[
+		("ICL_DISCORD_TOKEN", "a.b.c"),
+		("ICL_DISCORD_TOKEN_FILE", "token"),
+	]);
+	assert!(
+		stderr.contains("Both ICL_DISCORD_TOKEN and ICL_DISCORD_TOKEN_FILE are set"),
+		"{stderr}"
+	);
+
+	let stderr = run(&[("ICL_MQTT_BROKER_PORT", "mqtt")]);
+	assert!(
+		stderr.contains("ICL_MQTT_BROKER_PORT is \"mqtt\""),
+		"{stderr}"
+	);
+
+	let stderr = run(&[("ICL_MQTT_TOPIC", "chat/+")]);
+	assert!(
+		stderr.contains("`mqtt.topic` \"chat/+\" contains a wildcard (`+` or `#`), messages can't be published to a wildcard topic (set by ICL_MQTT_TOPIC)"),
+		"{stderr}"
+	);
+}
@@ -0,0 +1,88 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn bans(data_dir: &Path, args: &[&str]) -> Output {
+	let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config/valid.toml");
+
+	Command::new(env!("CARGO_BIN_EXE_discord-intergalactic-chat-link"))
+		.arg("bans")
+		.args(args)
+		.arg("--config")
+		.arg(config)
+		.arg("--data-dir")
+		.arg(data_dir)
+		.output()
+		.expect("Failed to run the binary")
+}
+
+fn data_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("icl-state-{name}-{}", std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+
+	dir
+}
+
+#[test]
+fn writes_keep_a_backup() {
+	let dir = data_dir("backup");
+
+	assert!(bans(&dir, &["add", "4242424242", "Spamming"])
+		.status
+		.success());
+	assert!(bans(&dir, &["add", "4343434343", "Spamming"])
+		.status
+		.success());
+
+	let current = fs::read_to_string(dir.join(".bans")).unwrap();
+	let backup = fs::read_to_string(dir.join(".bans.bak")).unwrap();
+	let temporary = dir.join(".bans.tmp").exists();
+	fs::remove_dir_all(&dir).unwrap();
+
+	assert!(
+		current.contains("4242424242") && current.contains("4343434343"),
+		"{current}"
+	);
+	assert!(
+		backup.contains("4242424242") && !backup.contains("4343434343"),
+		"{backup}"
+	);
+	assert!(!temporary);
+}
+
+#[test]
+fn corrupt_state_is_recovered_from_the_backup() {
+	let dir = data_dir("recover");
+
+	assert!(bans(&dir, &["add", "4242424242", "Spamming"])
+		.status
+		.success());
+	assert!(bans(&dir, &["add", "4343434343", "Spamming"])
+		.status
+		.success());
+	fs::write(dir.join(".bans"), "{\"list\": {").unwrap();
+
+	let output = bans(&dir, &["list"]);
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	let moved = dir.join(".bans.corrupt").exists();
+	fs::remove_dir_all(&dir).unwrap();
+
+	assert!(stdout.contains("is corrupt"), "{stdout}");
+	assert!(stdout.contains("4242424242: Spamming"), "{stdout}");
+	assert!(moved);
+}
+
+#[test]
+fn corrupt_state_without_a_backup_starts_empty() {
+	let dir = data_dir("empty");
+	fs::create_dir_all(&dir).unwrap();
+	fs::write(dir.join(".bans"), "not json").unwrap();
+
+	let output = bans(&dir, &["list"]);
+	let stdout = String::from_utf8(output.stdout).unwrap();
+	fs::remove_dir_all(&dir).unwrap();
+
+	assert!(output.status.success());
+	assert!(stdout.contains("starting empty"), "{stdout}");
+	assert!(stdout.contains("Nobody is banned"), "{stdout}");
+}